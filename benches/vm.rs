@@ -0,0 +1,56 @@
+/*
+times the vm on the lox programs in `benches/lox`, to compare value representations:
+
+  cargo bench --bench vm
+  cargo bench --bench vm --features nan-boxing
+
+the binary is driven as a subprocess (the crate has no library to link against), so
+each time includes process startup and compilation; both are small next to the runs.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 5;
+
+fn main() {
+  let representation = match cfg!(feature = "nan-boxing") {
+    true => "nan-boxed",
+    false => "enum",
+  };
+  println!("vm values: {} ({} runs each)", representation, RUNS);
+
+  let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches").join("lox");
+  let mut programs: Vec<PathBuf> = fs::read_dir(&directory)
+    .expect("missing benches/lox")
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|extension| extension == "hex"))
+    .collect();
+  programs.sort();
+
+  for program in programs {
+    let mut times: Vec<Duration> = (0..RUNS).map(|_| time(&program)).collect();
+    times.sort();
+    let name = program.file_stem().unwrap().to_string_lossy();
+    println!("{:<16} min {:>8.2?}  median {:>8.2?}", name, times[0], times[RUNS / 2]);
+  }
+}
+
+fn time(program: &Path) -> Duration {
+  let start = Instant::now();
+  let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+    .args(["--backend", "vm", "run"])
+    .arg(program)
+    .output()
+    .unwrap();
+  let elapsed = start.elapsed();
+  assert!(
+    output.status.success(),
+    "{} failed:\n{}",
+    program.display(),
+    String::from_utf8_lossy(&output.stderr)
+  );
+  elapsed
+}
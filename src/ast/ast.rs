@@ -1,21 +1,230 @@
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::shared::symbol::Symbol;
 use crate::visitor::Visitor;
 
-use super::tokens::{Token, TokenEnum};
+use super::span::Span;
+use super::tokens::Token;
+
+// identifies a variable use, so the resolver can record how far away its declaration is.
+pub type NodeId = usize;
+
+static NEXT_NODE_ID: AtomicUsize = AtomicUsize::new(0);
+
+// ids stay unique across programs, since the REPL keeps closures from earlier lines alive.
+fn next_node_id() -> NodeId {
+  NEXT_NODE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub struct Program {
+  pub statements: Vec<Statement>,
+}
+
+impl Program {
+  pub fn new(statements: Vec<Statement>) -> Program {
+    Program { statements }
+  }
+
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    visitor.visit_program(self)
+  }
+}
+
+#[allow(clippy::enum_variant_names)]
+pub enum Statement {
+  PrintStatement(PrintStatement),
+  ExpressionStatement(ExpressionStatement),
+  VariableStatement(VariableStatement),
+  BlockStatement(BlockStatement),
+  IfStatement(IfStatement),
+  WhileStatement(WhileStatement),
+  FunctionStatement(Rc<FunctionStatement>),
+  ReturnStatement(ReturnStatement),
+  ClassStatement(ClassStatement),
+}
+
+impl Statement {
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    match self {
+      Statement::PrintStatement(print_statement) => visitor.visit_print_statement(print_statement),
+      Statement::ExpressionStatement(expression_statement) => visitor.visit_expression_statement(expression_statement),
+      Statement::VariableStatement(variable_statement) => visitor.visit_variable_statement(variable_statement),
+      Statement::BlockStatement(block_statement) => visitor.visit_block_statement(block_statement),
+      Statement::IfStatement(if_statement) => visitor.visit_if_statement(if_statement),
+      Statement::WhileStatement(while_statement) => visitor.visit_while_statement(while_statement),
+      Statement::FunctionStatement(function_statement) => visitor.visit_function_statement(function_statement),
+      Statement::ReturnStatement(return_statement) => visitor.visit_return_statement(return_statement),
+      Statement::ClassStatement(class_statement) => visitor.visit_class_statement(class_statement),
+    }
+  }
+}
+
+// print expression ;
+pub struct PrintStatement {
+  pub expression: Box<Expression>,
+}
+
+impl PrintStatement {
+  pub fn new(expression: Expression) -> PrintStatement {
+    PrintStatement { expression: Box::new(expression) }
+  }
+}
+
+// expression ;
+pub struct ExpressionStatement {
+  pub expression: Box<Expression>,
+}
+
+impl ExpressionStatement {
+  pub fn new(expression: Expression) -> ExpressionStatement {
+    ExpressionStatement { expression: Box::new(expression) }
+  }
+}
+
+// var name = initializer ;
+pub struct VariableStatement {
+  pub name: Box<Token>,
+  pub initializer: Option<Box<Expression>>,
+}
+
+impl VariableStatement {
+  pub fn new(name: Box<Token>, initializer: Option<Expression>) -> VariableStatement {
+    VariableStatement { name, initializer: initializer.map(Box::new) }
+  }
+}
+
+// { statements }
+pub struct BlockStatement {
+  pub statements: Vec<Statement>,
+}
+
+impl BlockStatement {
+  pub fn new(statements: Vec<Statement>) -> BlockStatement {
+    BlockStatement { statements }
+  }
+}
+
+// if ( condition ) then_branch else else_branch
+pub struct IfStatement {
+  pub condition: Box<Expression>,
+  pub then_branch: Box<Statement>,
+  pub else_branch: Option<Box<Statement>>,
+}
+
+impl IfStatement {
+  pub fn new(condition: Expression, then_branch: Statement, else_branch: Option<Statement>) -> IfStatement {
+    IfStatement {
+      condition: Box::new(condition),
+      then_branch: Box::new(then_branch),
+      else_branch: else_branch.map(Box::new),
+    }
+  }
+}
+
+// while ( condition ) body
+// `for` loops are desugared into a block holding the initializer and one of these.
+pub struct WhileStatement {
+  pub condition: Box<Expression>,
+  pub body: Box<Statement>,
+}
+
+impl WhileStatement {
+  pub fn new(condition: Expression, body: Statement) -> WhileStatement {
+    WhileStatement { condition: Box::new(condition), body: Box::new(body) }
+  }
+}
+
+// fun name ( params ) { body }
+// shared behind an `Rc` because every function value created from it keeps it alive.
+pub struct FunctionStatement {
+  pub name: Box<Token>,
+  pub params: Vec<Token>,
+  pub body: Vec<Statement>,
+}
+
+impl FunctionStatement {
+  pub fn new(name: Box<Token>, params: Vec<Token>, body: Vec<Statement>) -> FunctionStatement {
+    FunctionStatement { name, params, body }
+  }
+}
+
+// return value ;
+pub struct ReturnStatement {
+  pub keyword: Box<Token>,
+  pub value: Option<Box<Expression>>,
+}
+
+impl ReturnStatement {
+  pub fn new(keyword: Box<Token>, value: Option<Expression>) -> ReturnStatement {
+    ReturnStatement { keyword, value: value.map(Box::new) }
+  }
+}
+
+// class name < superclass { methods }
+pub struct ClassStatement {
+  pub name: Box<Token>,
+  pub superclass: Option<Box<VariableExpression>>,
+  pub methods: Vec<Rc<FunctionStatement>>,
+}
+
+impl ClassStatement {
+  pub fn new(
+    name: Box<Token>,
+    superclass: Option<VariableExpression>,
+    methods: Vec<Rc<FunctionStatement>>,
+  ) -> ClassStatement {
+    ClassStatement { name, superclass: superclass.map(Box::new), methods }
+  }
+}
 
 pub enum Expression {
   BinaryExpression(BinaryExpression),
   UnaryExpression(UnaryExpression),
+  LogicalExpression(LogicalExpression),
+  CallExpression(CallExpression),
+  GetExpression(GetExpression),
+  SetExpression(SetExpression),
+  ThisExpression(ThisExpression),
+  SuperExpression(SuperExpression),
   GroupingExpression(GroupingExpression),
-  Literal(Literal),
+  VariableExpression(VariableExpression),
+  AssignmentExpression(AssignmentExpression),
+  Literal(LiteralExpression),
 }
 
 impl Expression {
   pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
     match self {
-      Expression::BinaryExpression(binary_expression) => visitor.visit_binary_expression(binary_expression),
-      Expression::UnaryExpression(unary_expression) => visitor.visit_unary_expression(unary_expression),
-      Expression::GroupingExpression(grouping_expression) => visitor.visit_grouping_expression(grouping_expression),
-      Expression::Literal(literal) => visitor.visit_literal(literal),
+      Expression::BinaryExpression(binary_expression) => binary_expression.accept(visitor),
+      Expression::UnaryExpression(unary_expression) => unary_expression.accept(visitor),
+      Expression::LogicalExpression(logical_expression) => logical_expression.accept(visitor),
+      Expression::CallExpression(call_expression) => call_expression.accept(visitor),
+      Expression::GetExpression(get_expression) => get_expression.accept(visitor),
+      Expression::SetExpression(set_expression) => set_expression.accept(visitor),
+      Expression::ThisExpression(this_expression) => this_expression.accept(visitor),
+      Expression::SuperExpression(super_expression) => super_expression.accept(visitor),
+      Expression::GroupingExpression(grouping_expression) => grouping_expression.accept(visitor),
+      Expression::VariableExpression(variable_expression) => variable_expression.accept(visitor),
+      Expression::AssignmentExpression(assignment_expression) => assignment_expression.accept(visitor),
+      Expression::Literal(literal_expression) => literal_expression.accept(visitor),
+    }
+  }
+
+  pub fn span(&self) -> Span {
+    match self {
+      Expression::BinaryExpression(binary_expression) => binary_expression.span,
+      Expression::UnaryExpression(unary_expression) => unary_expression.span,
+      Expression::LogicalExpression(logical_expression) => logical_expression.span,
+      Expression::CallExpression(call_expression) => call_expression.span,
+      Expression::GetExpression(get_expression) => get_expression.span,
+      Expression::SetExpression(set_expression) => set_expression.span,
+      Expression::ThisExpression(this_expression) => this_expression.span,
+      Expression::SuperExpression(super_expression) => super_expression.span,
+      Expression::GroupingExpression(grouping_expression) => grouping_expression.span,
+      Expression::VariableExpression(variable_expression) => variable_expression.span,
+      Expression::AssignmentExpression(assignment_expression) => assignment_expression.span,
+      Expression::Literal(literal_expression) => literal_expression.span,
     }
   }
 }
@@ -24,11 +233,13 @@ pub struct BinaryExpression {
   pub operator: Box<Token>,
   pub left: Box<Expression>,
   pub right: Box<Expression>,
+  pub span: Span,
 }
 
 impl BinaryExpression {
   pub fn new(operator: Box<Token>, left: Expression, right: Expression) -> BinaryExpression {
-    BinaryExpression { operator, left: Box::new(left), right: Box::new(right) }
+    let span = left.span().to(right.span());
+    BinaryExpression { operator, left: Box::new(left), right: Box::new(right), span }
   }
 
   pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
@@ -39,11 +250,13 @@ impl BinaryExpression {
 pub struct UnaryExpression {
   pub operator: Box<Token>,
   pub right: Box<Expression>,
+  pub span: Span,
 }
 
 impl UnaryExpression {
   pub fn new(operator: Box<Token>, right: Expression) -> UnaryExpression {
-    UnaryExpression { operator, right: Box::new(right) }
+    let span = operator.span.to(right.span());
+    UnaryExpression { operator, right: Box::new(right), span }
   }
 
   pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
@@ -51,13 +264,124 @@ impl UnaryExpression {
   }
 }
 
+// `and` / `or`, kept apart from binary expressions because the right side may never run.
+pub struct LogicalExpression {
+  pub operator: Box<Token>,
+  pub left: Box<Expression>,
+  pub right: Box<Expression>,
+  pub span: Span,
+}
+
+impl LogicalExpression {
+  pub fn new(operator: Box<Token>, left: Expression, right: Expression) -> LogicalExpression {
+    let span = left.span().to(right.span());
+    LogicalExpression { operator, left: Box::new(left), right: Box::new(right), span }
+  }
+
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    visitor.visit_logical_expression(self)
+  }
+}
+
+pub struct CallExpression {
+  pub callee: Box<Expression>,
+  pub paren: Box<Token>, // the closing parenthesis, used to report errors in the call
+  pub arguments: Vec<Expression>,
+  pub span: Span,
+}
+
+impl CallExpression {
+  pub fn new(callee: Expression, paren: Box<Token>, arguments: Vec<Expression>) -> CallExpression {
+    let span = callee.span().to(paren.span);
+    CallExpression { callee: Box::new(callee), paren, arguments, span }
+  }
+
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    visitor.visit_call_expression(self)
+  }
+}
+
+// object . name
+pub struct GetExpression {
+  pub object: Box<Expression>,
+  pub name: Box<Token>,
+  pub span: Span,
+}
+
+impl GetExpression {
+  pub fn new(object: Expression, name: Box<Token>) -> GetExpression {
+    let span = object.span().to(name.span);
+    GetExpression { object: Box::new(object), name, span }
+  }
+
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    visitor.visit_get_expression(self)
+  }
+}
+
+// object . name = value
+pub struct SetExpression {
+  pub object: Box<Expression>,
+  pub name: Box<Token>,
+  pub value: Box<Expression>,
+  pub span: Span,
+}
+
+impl SetExpression {
+  pub fn new(object: Expression, name: Box<Token>, value: Expression) -> SetExpression {
+    let span = object.span().to(value.span());
+    SetExpression { object: Box::new(object), name, value: Box::new(value), span }
+  }
+
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    visitor.visit_set_expression(self)
+  }
+}
+
+pub struct ThisExpression {
+  pub id: NodeId,
+  pub keyword: Box<Token>,
+  pub span: Span,
+}
+
+impl ThisExpression {
+  pub fn new(keyword: Box<Token>) -> ThisExpression {
+    let span = keyword.span;
+    ThisExpression { id: next_node_id(), keyword, span }
+  }
+
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    visitor.visit_this_expression(self)
+  }
+}
+
+// super . method
+pub struct SuperExpression {
+  pub id: NodeId,
+  pub keyword: Box<Token>,
+  pub method: Box<Token>,
+  pub span: Span,
+}
+
+impl SuperExpression {
+  pub fn new(keyword: Box<Token>, method: Box<Token>) -> SuperExpression {
+    let span = keyword.span.to(method.span);
+    SuperExpression { id: next_node_id(), keyword, method, span }
+  }
+
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    visitor.visit_super_expression(self)
+  }
+}
+
 pub struct GroupingExpression {
   pub expression: Box<Expression>,
+  pub span: Span, // includes the parentheses
 }
 
 impl GroupingExpression {
-  pub fn new(expression: Expression) -> GroupingExpression {
-    GroupingExpression { expression: Box::new(expression) }
+  pub fn new(expression: Expression, span: Span) -> GroupingExpression {
+    GroupingExpression { expression: Box::new(expression), span }
   }
 
   pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
@@ -65,8 +389,59 @@ impl GroupingExpression {
   }
 }
 
+pub struct VariableExpression {
+  pub id: NodeId,
+  pub name: Box<Token>,
+  pub span: Span,
+}
+
+impl VariableExpression {
+  pub fn new(name: Box<Token>) -> VariableExpression {
+    let span = name.span;
+    VariableExpression { id: next_node_id(), name, span }
+  }
+
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    visitor.visit_variable_expression(self)
+  }
+}
+
+pub struct AssignmentExpression {
+  pub id: NodeId,
+  pub name: Box<Token>,
+  pub value: Box<Expression>,
+  pub span: Span,
+}
+
+impl AssignmentExpression {
+  pub fn new(name: Box<Token>, value: Expression) -> AssignmentExpression {
+    let span = name.span.to(value.span());
+    AssignmentExpression { id: next_node_id(), name, value: Box::new(value), span }
+  }
+
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    visitor.visit_assignment_expression(self)
+  }
+}
+
+pub struct LiteralExpression {
+  pub value: Literal,
+  pub span: Span,
+}
+
+impl LiteralExpression {
+  pub fn new(value: Literal, span: Span) -> LiteralExpression {
+    LiteralExpression { value, span }
+  }
+
+  pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
+    self.value.accept(visitor)
+  }
+}
+
+#[allow(clippy::enum_variant_names)]
 pub enum Literal {
-  StringLiteral(String),
+  StringLiteral(Symbol),
   NumberLiteral(f64),
   BooleanLiteral(bool),
   NullLiteral,
@@ -1,4 +1,7 @@
+#[allow(clippy::module_inception)]
 mod ast;
+pub mod span;
 pub mod tokens;
 
 pub use ast::*;
+pub use span::Span;
@@ -0,0 +1,53 @@
+// a half-open range of byte offsets into the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+}
+
+impl Span {
+  pub fn new(start: usize, end: usize) -> Span {
+    Span { start, end }
+  }
+
+  // the smallest span covering both `self` and `other`.
+  pub fn to(&self, other: Span) -> Span {
+    Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+  }
+
+  #[allow(dead_code)]
+  pub fn len(&self) -> usize {
+    self.end - self.start
+  }
+
+  #[allow(dead_code)]
+  pub fn is_empty(&self) -> bool {
+    self.start == self.end
+  }
+
+  pub fn text<'a>(&self, source: &'a str) -> &'a str {
+    &source[self.start..self.end]
+  }
+
+  // 1-based line and column (in characters) of the span start.
+  #[allow(dead_code)]
+  pub fn line_column(&self, source: &str) -> (usize, usize) {
+    line_column(source, self.start)
+  }
+}
+
+// 1-based line and column (in characters) of a byte offset.
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+  let before = &source[..offset];
+  let line = before.matches('\n').count() + 1;
+  let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+  let column = source[line_start..offset].chars().count() + 1;
+  (line, column)
+}
+
+// the full text of the line containing `offset`, without the line break.
+pub fn line_text(source: &str, offset: usize) -> &str {
+  let line_start = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+  let line_end = source[offset..].find('\n').map_or(source.len(), |index| offset + index);
+  source[line_start..line_end].trim_end_matches('\r')
+}
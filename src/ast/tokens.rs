@@ -43,24 +43,35 @@ pub enum TokenEnum {
   Super,  // 'super'
   This,   // 'this'
   True,   // 'true
+  Var,    // 'var'
+  While,  // 'while'
 
   EndOfFile, // end of file
 }
 
+use std::fmt::{Display, Formatter};
+
+use super::span::Span;
+use crate::shared::symbol::Symbol;
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
   pub kind: TokenEnum,
-  pub lexeme: String, // the source code,
-  pub literal: String,
-  pub line: usize,
+  pub lexeme: Symbol, // the source code,
+  pub literal: Symbol,
+  pub span: Span,    // byte offsets of the lexeme in the source
+  pub line: usize,   // 1-based line where the token starts
+  pub column: usize, // 1-based column (in characters) where the token starts
 }
 
 impl Token {
-  pub fn new(kind: TokenEnum, lexeme: String, literal: String, line: usize) -> Token {
-    Token { kind, lexeme, literal, line }
+  pub fn new(kind: TokenEnum, lexeme: Symbol, literal: Symbol, span: Span, line: usize, column: usize) -> Token {
+    Token { kind, lexeme, literal, span, line, column }
   }
+}
 
-  pub fn to_string(&self) -> String {
-    format!("{:?} {} {}", self.kind, self.lexeme, self.literal)
+impl Display for Token {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:?} {} {}", self.kind, self.lexeme, self.literal)
   }
 }
@@ -1,23 +1,98 @@
 use clap::Arg;
+use clap::ArgAction;
 use clap::Command;
 
 pub fn command_line() -> clap::ArgMatches {
-  let run_subcommand = Command::new("run")
-    .about("run a lox file.")
-    .arg(Arg::new("file").help("the lox file to execute.").required(true));
+  let run_subcommand = Command::new("run").about("run a lox file.").arg(
+    Arg::new("file")
+      .help("the lox file to execute, a compiled `.hexc` or `.hexasm` file, or `-` to read from stdin.")
+      .required(true),
+  );
   let compile_subcommand = Command::new("compile")
     .about("compile a lox file to bytecode.")
     .arg(Arg::new("file").help("the lox file to compile.").required(true));
+  let asm_subcommand = Command::new("asm").about("assemble a `.hexasm` file to bytecode.").arg(
+    Arg::new("file")
+      .help("the assembly file, or `-` to read from stdin.")
+      .required(true),
+  );
+  let disasm_subcommand = Command::new("disasm")
+    .about("print the bytecode of a lox file or a compiled `.hexc` file.")
+    .arg(Arg::new("file").help("the file to disassemble.").required(true));
+  let test_suite_subcommand = Command::new("test-suite")
+    .about("run every lox file in a directory and check it against its `// expect:` annotations.")
+    .arg(
+      Arg::new("directory")
+        .help("the directory containing the test files.")
+        .required(true),
+    );
 
-  let maches = Command::new("lox")
+  Command::new("lox")
     .version("0.1.0")
     .author("Yazalde Filimone <yazaldefilimon@gmail.com>")
     .about("The Lox Language")
     .subcommand_required(false)
     .arg_required_else_help(false)
+    .arg(
+      Arg::new("loose-truthiness")
+        .long("loose-truthiness")
+        .global(true)
+        .action(ArgAction::SetTrue)
+        .help("also treat 0 and \"\" as falsy, instead of only nil and false."),
+    )
+    .arg(
+      Arg::new("backend")
+        .long("backend")
+        .global(true)
+        .value_parser(["tree-walker", "vm"])
+        .default_value("vm")
+        .help("how `run` and `test-suite` execute programs; the REPL always uses the tree-walker."),
+    )
+    .arg(
+      Arg::new("trace")
+        .long("trace")
+        .global(true)
+        .action(ArgAction::SetTrue)
+        .help(
+          "print the vm stack and each instruction to stderr as it executes; ignored with `--backend tree-walker`.",
+        ),
+    )
+    .arg(
+      Arg::new("gc-stress")
+        .long("gc-stress")
+        .global(true)
+        .action(ArgAction::SetTrue)
+        .help("run the vm's garbage collector before every allocation, to shake out rooting bugs."),
+    )
+    .arg(
+      Arg::new("gc-growth-factor")
+        .long("gc-growth-factor")
+        .global(true)
+        .value_name("factor")
+        .value_parser(parse_growth_factor)
+        .help("how much the vm heap may grow after a collection before the next one [default: 2]."),
+    )
+    .arg(
+      Arg::new("stats")
+        .long("stats")
+        .global(true)
+        .action(ArgAction::SetTrue)
+        .help(
+          "after `run`, print inline cache hit rates, the number of shapes and gc counters to stderr; ignored with `--backend tree-walker`.",
+        ),
+    )
     .subcommand(run_subcommand)
     .subcommand(compile_subcommand)
-    .get_matches();
+    .subcommand(asm_subcommand)
+    .subcommand(disasm_subcommand)
+    .subcommand(test_suite_subcommand)
+    .get_matches()
+}
 
-  maches
+// the heap has to be allowed to grow, or every allocation would trigger a collection.
+fn parse_growth_factor(text: &str) -> Result<f64, String> {
+  match text.parse::<f64>() {
+    Ok(factor) if factor > 1.0 => Ok(factor),
+    _ => Err(String::from("expected a number greater than 1")),
+  }
 }
@@ -1,28 +1,85 @@
-use crate::ast::tokens::Token;
+use super::render::{render, use_color};
+use super::{Diagnostic, DiagnosticKind};
+use crate::ast::tokens::{Token, TokenEnum};
+use crate::ast::Span;
 
+// collects diagnostics instead of printing them, so callers decide when (and whether) to show them.
 pub struct ErrorHandler {
   pub had_error: bool,
   pub path_name: String,
+  pub source: String, // the text spans point into, used to render snippets
+  pub diagnostics: Vec<Diagnostic>,
 }
 
 impl ErrorHandler {
   pub fn new(path_name: &str) -> Self {
-    ErrorHandler { had_error: false, path_name: path_name.to_owned() }
+    ErrorHandler::with_source(path_name, "")
   }
 
+  pub fn with_source(path_name: &str, source: &str) -> Self {
+    ErrorHandler { had_error: false, path_name: path_name.to_owned(), source: source.to_owned(), diagnostics: vec![] }
+  }
+
+  pub fn set_source(&mut self, source: &str) {
+    self.source = source.to_owned();
+  }
+
+  #[allow(dead_code)]
   pub fn report(&mut self, line: usize, _where: &str, message: &str) {
-    let report_message = format!("error: {}\n\tat {}, line: {}\n", message, self.path_name, line);
-    println!("{}", report_message);
-    self.had_error = true;
+    self.push(Diagnostic::error(message, line, _where));
   }
 
+  #[allow(dead_code)]
   pub fn error(&mut self, line: usize, message: &str) {
-    self.report(line, "<unknown>", message);
+    self.report(line, "", message);
+  }
+
+  pub fn error_at(&mut self, span: Span, line: usize, message: &str) {
+    self.push(Diagnostic::error(message, line, "").with_span(span));
   }
 
   pub fn report_rich(&mut self, token: &Token, message: &str) {
-    let report_message = format!("error: {}\n\tat {}, line: {}\n", message, self.path_name, token.line);
-    println!("{}", report_message);
-    self.had_error = true;
+    self.push(ErrorHandler::token_diagnostic(token, message));
+  }
+
+  // an error diagnostic pointing at `token`, ready for extra labels and notes.
+  pub fn token_diagnostic(token: &Token, message: &str) -> Diagnostic {
+    let location = match token.kind {
+      TokenEnum::EndOfFile => String::from("at end"),
+      _ => format!("at '{}'", token.lexeme),
+    };
+    Diagnostic::error(message, token.line, &location).with_span(token.span)
+  }
+
+  pub fn push(&mut self, diagnostic: Diagnostic) {
+    if diagnostic.kind == DiagnosticKind::Error {
+      self.had_error = true;
+    }
+    self.diagnostics.push(diagnostic);
+  }
+
+  pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+    std::mem::take(&mut self.diagnostics)
+  }
+
+  pub fn render(&self, diagnostic: &Diagnostic) -> String {
+    render(diagnostic, &self.path_name, &self.source, use_color())
+  }
+
+  pub fn print(&self, diagnostic: &Diagnostic) {
+    println!("{}", self.render(diagnostic));
+  }
+
+  // prints and forgets every collected diagnostic.
+  pub fn flush(&mut self) {
+    for diagnostic in self.take_diagnostics() {
+      self.print(&diagnostic);
+    }
+  }
+
+  // clears the error state between independent runs (e.g. REPL lines).
+  pub fn reset(&mut self) {
+    self.had_error = false;
+    self.diagnostics.clear();
   }
 }
@@ -1,18 +1,86 @@
 use crate::ast::tokens::Token;
+use crate::ast::Span;
 use error_handler::ErrorHandler;
 
 pub mod error_handler;
+pub mod render;
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum DiagnosticKind {
   Error,
   Warning,
+  #[allow(dead_code)] // rendered like the others; nothing reports one yet
   Info,
 }
 
+impl DiagnosticKind {
+  pub fn name(&self) -> &'static str {
+    match self {
+      DiagnosticKind::Error => "error",
+      DiagnosticKind::Warning => "warning",
+      DiagnosticKind::Info => "info",
+    }
+  }
+}
+
+// a secondary annotation pointing at related code, e.g. the '(' an unclosed group started at.
+#[derive(Debug, Clone)]
+pub struct Label {
+  pub span: Span,
+  pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+  pub kind: DiagnosticKind,
+  pub message: String,
+  pub line: usize,
+  pub location: String,   // e.g. "at 'foo'" or "at end", empty when unknown
+  pub span: Option<Span>, // the primary range, underlined with carets
+  pub labels: Vec<Label>,
+  pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+  pub fn new(kind: DiagnosticKind, message: &str, line: usize, location: &str) -> Self {
+    Diagnostic {
+      kind,
+      message: message.to_owned(),
+      line,
+      location: location.to_owned(),
+      span: None,
+      labels: vec![],
+      notes: vec![],
+    }
+  }
+
+  pub fn error(message: &str, line: usize, location: &str) -> Self {
+    Diagnostic::new(DiagnosticKind::Error, message, line, location)
+  }
+
+  pub fn with_span(mut self, span: Span) -> Self {
+    self.span = Some(span);
+    self
+  }
+
+  pub fn with_label(mut self, span: Span, message: &str) -> Self {
+    self.labels.push(Label { span, message: message.to_owned() });
+    self
+  }
+
+  pub fn with_note(mut self, note: &str) -> Self {
+    self.notes.push(note.to_owned());
+    self
+  }
+}
+
+// the baseline front end's error sink; the scanner and parser now own an `ErrorHandler` directly.
+#[allow(dead_code)]
 pub struct Diagnostics {
   pub error_handler: ErrorHandler,
 }
 
+#[allow(dead_code)]
 impl Diagnostics {
   pub fn new(path_name: &str) -> Self {
     Diagnostics { error_handler: ErrorHandler::new(path_name) }
@@ -25,6 +93,7 @@ impl Diagnostics {
   pub fn report(&mut self, line: usize, _where: &str, message: &str) {
     self.error_handler.report(line, _where, message);
   }
+
   pub fn error(&mut self, line: usize, message: &str) {
     self.error_handler.error(line, message);
   }
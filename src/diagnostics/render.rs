@@ -0,0 +1,149 @@
+use std::io::IsTerminal;
+
+use super::{Diagnostic, DiagnosticKind};
+use crate::ast::span::{line_column, line_text};
+use crate::ast::Span;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+// colors only make sense on a terminal; `NO_COLOR` opts out as usual.
+pub fn use_color() -> bool {
+  std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+struct Painter {
+  color: bool,
+}
+
+impl Painter {
+  fn paint(&self, text: &str, style: &str) -> String {
+    if !self.color {
+      return text.to_owned();
+    }
+    format!("{}{}{}", style, text, RESET)
+  }
+}
+
+// one underlined range on a source line.
+struct Annotation<'a> {
+  line: usize,
+  column: usize,
+  width: usize,
+  message: &'a str,
+  primary: bool,
+}
+
+/*
+renders a diagnostic the way rustc does:
+
+error: Expected ';' after value.
+ --> main.hex:1:12
+  |
+1 | print 1 + 2
+  |            ^
+  |
+  = note: ...
+*/
+pub fn render(diagnostic: &Diagnostic, path_name: &str, source: &str, color: bool) -> String {
+  let painter = Painter { color };
+  let kind_style = match diagnostic.kind {
+    DiagnosticKind::Error => RED,
+    DiagnosticKind::Warning => YELLOW,
+    DiagnosticKind::Info => CYAN,
+  };
+  let mut output = format!(
+    "{}{}\n",
+    painter.paint(diagnostic.kind.name(), kind_style),
+    painter.paint(&format!(": {}", diagnostic.message), BOLD)
+  );
+
+  let mut annotations = vec![];
+  if let Some(span) = diagnostic.span {
+    annotations.push(annotate(source, span, "", true));
+  }
+  for label in &diagnostic.labels {
+    annotations.push(annotate(source, label.span, &label.message, false));
+  }
+
+  let (line, column) = match annotations.first() {
+    Some(annotation) => (annotation.line, annotation.column),
+    None => (diagnostic.line, 0),
+  };
+  let last_line = annotations
+    .iter()
+    .map(|annotation| annotation.line)
+    .max()
+    .unwrap_or(line);
+  let gutter = " ".repeat(last_line.to_string().len());
+  let arrow = painter.paint("-->", BLUE);
+  match (line, column) {
+    (0, _) => output.push_str(&format!("{}{} {}\n", gutter, arrow, path_name)),
+    (_, 0) => output.push_str(&format!("{}{} {}:{}\n", gutter, arrow, path_name, line)),
+    _ => output.push_str(&format!("{}{} {}:{}:{}\n", gutter, arrow, path_name, line, column)),
+  }
+
+  let bar = painter.paint("|", BLUE);
+  if !annotations.is_empty() {
+    output.push_str(&format!("{} {}\n", gutter, bar));
+    annotations.sort_by_key(|annotation| (annotation.line, !annotation.primary));
+    let mut index = 0;
+    while index < annotations.len() {
+      let current_line = annotations[index].line;
+      let text = line_text_at(source, current_line);
+      let line_number = painter.paint(&format!("{:>width$}", current_line, width = gutter.len()), BLUE);
+      output.push_str(&format!("{} {} {}\n", line_number, bar, text));
+      while index < annotations.len() && annotations[index].line == current_line {
+        let annotation = &annotations[index];
+        let (marker, style) = match annotation.primary {
+          true => ("^", kind_style),
+          false => ("-", BLUE),
+        };
+        let underline = format!("{}{}", marker.repeat(annotation.width), prefixed(annotation.message));
+        let padding = " ".repeat(annotation.column - 1);
+        output.push_str(&format!(
+          "{} {} {}{}\n",
+          gutter,
+          bar,
+          padding,
+          painter.paint(&underline, style)
+        ));
+        index += 1;
+      }
+    }
+  }
+
+  if !annotations.is_empty() && !diagnostic.notes.is_empty() {
+    output.push_str(&format!("{} {}\n", gutter, bar));
+  }
+  for note in &diagnostic.notes {
+    let note = painter.paint(&format!("note: {}", note), BOLD);
+    output.push_str(&format!("{} {} {}\n", gutter, painter.paint("=", BLUE), note));
+  }
+  output
+}
+
+fn prefixed(message: &str) -> String {
+  if message.is_empty() {
+    return String::new();
+  }
+  format!(" {}", message)
+}
+
+// spans reaching past the end of their first line are underlined up to the line end.
+fn annotate<'a>(source: &str, span: Span, message: &'a str, primary: bool) -> Annotation<'a> {
+  let start = span.start.min(source.len());
+  let (line, column) = line_column(source, start);
+  let rest = line_text(source, start).chars().count() - (column - 1);
+  let end = span.end.clamp(start, source.len());
+  let width = source[start..end].chars().count().min(rest).max(1);
+  Annotation { line, column, width, message, primary }
+}
+
+fn line_text_at(source: &str, line: usize) -> &str {
+  source.split('\n').nth(line - 1).unwrap_or("").trim_end_matches('\r')
+}
@@ -0,0 +1,61 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::function::Function;
+use crate::shared::symbol::Symbol;
+use crate::values::Value;
+
+pub struct Class {
+  pub name: String,
+  pub superclass: Option<Rc<Class>>,
+  pub methods: HashMap<Symbol, Rc<Function>>,
+}
+
+impl Class {
+  pub fn new(name: &str, superclass: Option<Rc<Class>>, methods: HashMap<Symbol, Rc<Function>>) -> Self {
+    Class { name: name.to_owned(), superclass, methods }
+  }
+
+  // looks through the superclass chain when this class does not define the method itself.
+  pub fn find_method(&self, name: &Symbol) -> Option<Rc<Function>> {
+    if let Some(method) = self.methods.get(name) {
+      return Some(Rc::clone(method));
+    }
+    self.superclass.as_ref()?.find_method(name)
+  }
+
+  // calling a class takes the arguments of its `init` method, if it has one.
+  pub fn arity(&self) -> usize {
+    match self.find_method(&Symbol::intern("init")) {
+      Some(initializer) => initializer.arity(),
+      None => 0,
+    }
+  }
+}
+
+pub struct Instance {
+  pub class: Rc<Class>,
+  fields: HashMap<Symbol, Value>,
+}
+
+impl Instance {
+  pub fn new(class: Rc<Class>) -> Self {
+    Instance { class, fields: HashMap::new() }
+  }
+
+  // fields shadow methods; methods come back bound to this instance.
+  pub fn get(instance: &Rc<RefCell<Instance>>, name: &Symbol) -> Option<Value> {
+    if let Some(value) = instance.borrow().fields.get(name) {
+      return Some(value.clone());
+    }
+    let method = instance.borrow().class.find_method(name)?;
+    Some(Value::create_function(
+      method.bind(Value::Instance(Rc::clone(instance))),
+    ))
+  }
+
+  pub fn set(&mut self, name: &Symbol, value: Value) {
+    self.fields.insert(name.clone(), value);
+  }
+}
@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::shared::symbol::Symbol;
+use crate::values::Value;
+
+// a scope of variable bindings, chained to the scope that encloses it.
+pub struct Environment {
+  values: HashMap<Symbol, Value>,
+  enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+  pub fn new() -> Self {
+    Environment { values: HashMap::new(), enclosing: None }
+  }
+
+  pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Self {
+    Environment { values: HashMap::new(), enclosing: Some(enclosing) }
+  }
+
+  // declaring an existing name in the same scope just replaces its value.
+  pub fn define(&mut self, name: &Symbol, value: Value) {
+    self.values.insert(name.clone(), value);
+  }
+
+  pub fn get(&self, name: &Symbol) -> Option<Value> {
+    if let Some(value) = self.values.get(name) {
+      return Some(value.clone());
+    }
+    match &self.enclosing {
+      Some(enclosing) => enclosing.borrow().get(name),
+      None => None,
+    }
+  }
+
+  // reads a variable the resolver found `distance` scopes out.
+  pub fn get_at(environment: &Rc<RefCell<Environment>>, distance: usize, name: &Symbol) -> Option<Value> {
+    let ancestor = Environment::ancestor(environment, distance);
+    let value = ancestor.borrow().values.get(name).cloned();
+    value
+  }
+
+  pub fn assign_at(environment: &Rc<RefCell<Environment>>, distance: usize, name: &Symbol, value: Value) {
+    let ancestor = Environment::ancestor(environment, distance);
+    ancestor.borrow_mut().define(name, value);
+  }
+
+  fn ancestor(environment: &Rc<RefCell<Environment>>, distance: usize) -> Rc<RefCell<Environment>> {
+    let mut current = Rc::clone(environment);
+    for _ in 0..distance {
+      let enclosing = current
+        .borrow()
+        .enclosing
+        .clone()
+        .expect("resolved depth exceeds the scope chain");
+      current = enclosing;
+    }
+    current
+  }
+
+  // returns false when the variable is not declared in any scope.
+  pub fn assign(&mut self, name: &Symbol, value: Value) -> bool {
+    if let Some(slot) = self.values.get_mut(name) {
+      *slot = value;
+      return true;
+    }
+    match &self.enclosing {
+      Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+      None => false,
+    }
+  }
+}
@@ -1,185 +1,423 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::class::{Class, Instance};
+use super::environment::Environment;
+use super::function::{clock, Function, NativeFunction};
+use super::RuntimeError;
 use crate::ast;
-use crate::ast::tokens::{Token, TokenEnum};
+use crate::ast::tokens::TokenEnum;
 use crate::diagnostics::error_handler::ErrorHandler;
-use crate::values::Value;
+use crate::shared::constants::MAX_CALL_DEPTH;
+use crate::shared::output::Output;
+use crate::shared::symbol::Symbol;
+use crate::values::{Truthiness, Value};
 use crate::visitor::Visitor;
 
+pub type EvaluatorResult = Result<Value, RuntimeError>;
+
 pub struct Evaluator {
   pub error_handler: ErrorHandler,
+  pub truthiness: Truthiness,
+  pub output: Output,
+  globals: Rc<RefCell<Environment>>,
+  environment: Rc<RefCell<Environment>>,
+  // scope distance of every resolved local variable use; anything missing is a global.
+  locals: HashMap<ast::NodeId, usize>,
+  // set by a `return` statement; statements stop executing until the enclosing call takes it.
+  return_value: Option<Value>,
+  call_depth: usize,
 }
 
-impl Visitor<Value> for Evaluator {
-  fn visit(&mut self, ast: &ast::Expression) -> Value {
-    match ast {
-      ast::Expression::BinaryExpression(binary_expression) => self.visit_binary_expression(binary_expression),
-      ast::Expression::UnaryExpression(unary_expression) => self.visit_unary_expression(unary_expression),
-      ast::Expression::GroupingExpression(grouping_expression) => self.visit_grouping_expression(grouping_expression),
-      ast::Expression::Literal(literal) => self.visit_literal(literal),
-    }
+impl Visitor<EvaluatorResult> for Evaluator {
+  fn visit(&mut self, ast: &ast::Expression) -> EvaluatorResult {
+    ast.accept(self)
   }
 
-  fn visit_binary_expression(&mut self, binary_expression: &ast::BinaryExpression) -> Value {
-    let left = self.evaluate(&binary_expression.left);
-    let right = self.evaluate(&binary_expression.right);
-    if !self.check_suport_binary_operator(&binary_expression.operator.kind, &left) {
-      self.runtime_error(
-        &*binary_expression.operator,
-        "Unsupported operator for left expression, expected a number or a string.",
-      );
+  // the value of a program is the value of its last statement (used by the REPL).
+  fn visit_program(&mut self, program: &ast::Program) -> EvaluatorResult {
+    let mut result = Value::create_nil();
+    for statement in &program.statements {
+      result = self.execute(statement)?;
+      if self.return_value.take().is_some() {
+        break;
+      }
     }
-    if !self.check_suport_binary_operator(&binary_expression.operator.kind, &right) {
-      self.runtime_error(
-        &*binary_expression.operator,
-        "Unsupported operator, for right expression, expected a number or a string.",
-      );
+    Ok(result)
+  }
+
+  fn visit_statement(&mut self, statement: &ast::Statement) -> EvaluatorResult {
+    statement.accept(self)
+  }
+
+  fn visit_print_statement(&mut self, print_statement: &ast::PrintStatement) -> EvaluatorResult {
+    let value = self.evaluate(&print_statement.expression)?;
+    self.output.print_line(&value.to_string());
+    Ok(Value::create_nil())
+  }
+
+  fn visit_expression_statement(&mut self, expression_statement: &ast::ExpressionStatement) -> EvaluatorResult {
+    self.evaluate(&expression_statement.expression)
+  }
+
+  fn visit_variable_statement(&mut self, variable_statement: &ast::VariableStatement) -> EvaluatorResult {
+    let value = match &variable_statement.initializer {
+      Some(initializer) => self.evaluate(initializer)?,
+      None => Value::create_nil(),
+    };
+    self
+      .environment
+      .borrow_mut()
+      .define(&variable_statement.name.lexeme, value);
+    Ok(Value::create_nil())
+  }
+
+  fn visit_block_statement(&mut self, block_statement: &ast::BlockStatement) -> EvaluatorResult {
+    let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+    self.execute_block(&block_statement.statements, environment)?;
+    Ok(Value::create_nil())
+  }
+
+  fn visit_if_statement(&mut self, if_statement: &ast::IfStatement) -> EvaluatorResult {
+    let condition = self.evaluate(&if_statement.condition)?;
+    if self.is_truthy(&condition) {
+      self.execute(&if_statement.then_branch)?;
+    } else if let Some(else_branch) = &if_statement.else_branch {
+      self.execute(else_branch)?;
     }
+    Ok(Value::create_nil())
+  }
 
-    match (&binary_expression.operator.kind, &left, &right) {
-      (TokenEnum::Plus, Value::Number(left), Value::Number(right)) => {
-        return Value::create_number(*left + *right);
+  fn visit_while_statement(&mut self, while_statement: &ast::WhileStatement) -> EvaluatorResult {
+    loop {
+      let condition = self.evaluate(&while_statement.condition)?;
+      if !self.is_truthy(&condition) {
+        break;
       }
-      (TokenEnum::Plus, Value::String(left), Value::String(right)) => {
-        return Value::create_string(left.to_owned() + right.as_str());
-      }
-      (TokenEnum::Minus, Value::Number(left), Value::Number(right)) => {
-        return Value::create_number(*left - *right);
-      }
-      (TokenEnum::Star, Value::Number(left), Value::Number(right)) => {
-        return Value::create_number(*left * *right);
+      self.execute(&while_statement.body)?;
+      if self.return_value.is_some() {
+        break;
       }
-      (TokenEnum::Slash, Value::Number(left), Value::Number(right)) => {
-        return Value::create_number(*left / *right);
-      }
-      (TokenEnum::Greater, Value::Number(left), Value::Number(right)) => {
-        return Value::create_boolean(*left > *right);
+    }
+    Ok(Value::create_nil())
+  }
+
+  fn visit_function_statement(&mut self, function_statement: &Rc<ast::FunctionStatement>) -> EvaluatorResult {
+    let function = Function::new(Rc::clone(function_statement), Rc::clone(&self.environment), false);
+    self
+      .environment
+      .borrow_mut()
+      .define(&function_statement.name.lexeme, Value::create_function(function));
+    Ok(Value::create_nil())
+  }
+
+  fn visit_return_statement(&mut self, return_statement: &ast::ReturnStatement) -> EvaluatorResult {
+    let value = match &return_statement.value {
+      Some(value) => self.evaluate(value)?,
+      None => Value::create_nil(),
+    };
+    self.return_value = Some(value);
+    Ok(Value::create_nil())
+  }
+
+  fn visit_class_statement(&mut self, class_statement: &ast::ClassStatement) -> EvaluatorResult {
+    let superclass = match &class_statement.superclass {
+      Some(superclass) => match self.visit_variable_expression(superclass)? {
+        Value::Class(class) => Some(class),
+        _ => return Err(RuntimeError::invalid_superclass(&superclass.name)),
+      },
+      None => None,
+    };
+
+    // methods of a subclass close over a scope that binds `super` to the superclass.
+    let mut closure = Rc::clone(&self.environment);
+    if let Some(superclass) = &superclass {
+      let mut environment = Environment::new_enclosed(closure);
+      environment.define(&Symbol::intern("super"), Value::Class(Rc::clone(superclass)));
+      closure = Rc::new(RefCell::new(environment));
+    }
+
+    let mut methods = HashMap::new();
+    for method in &class_statement.methods {
+      let is_initializer = method.name.lexeme == "init";
+      let function = Function::new(Rc::clone(method), Rc::clone(&closure), is_initializer);
+      methods.insert(method.name.lexeme.clone(), Rc::new(function));
+    }
+    let class = Class::new(&class_statement.name.lexeme, superclass, methods);
+    self
+      .environment
+      .borrow_mut()
+      .define(&class_statement.name.lexeme, Value::create_class(class));
+    Ok(Value::create_nil())
+  }
+
+  fn visit_binary_expression(&mut self, binary_expression: &ast::BinaryExpression) -> EvaluatorResult {
+    let left = self.evaluate(&binary_expression.left)?;
+    let right = self.evaluate(&binary_expression.right)?;
+    let operator = &binary_expression.operator;
+
+    match (&operator.kind, &left, &right) {
+      (TokenEnum::EqualEqual, _, _) => Ok(Value::create_boolean(self.is_equal(&left, &right))),
+      (TokenEnum::BangEqual, _, _) => Ok(Value::create_boolean(!self.is_equal(&left, &right))),
+      (TokenEnum::Plus, Value::Number(left), Value::Number(right)) => Ok(Value::create_number(*left + *right)),
+      (TokenEnum::Plus, Value::String(left), Value::String(right)) => {
+        Ok(Value::create_string(Symbol::intern(&format!("{}{}", left, right))))
       }
-      (TokenEnum::Less, Value::Number(left), Value::Number(right)) => {
-        return Value::create_boolean(*left < *right);
+      (TokenEnum::Plus, _, _) => {
+        let message = "Operands must be two numbers or two strings.";
+        Err(RuntimeError::type_error(operator, message))
       }
+      (TokenEnum::Minus, Value::Number(left), Value::Number(right)) => Ok(Value::create_number(*left - *right)),
+      (TokenEnum::Star, Value::Number(left), Value::Number(right)) => Ok(Value::create_number(*left * *right)),
+      (TokenEnum::Slash, Value::Number(left), Value::Number(right)) => Ok(Value::create_number(*left / *right)),
+      (TokenEnum::Greater, Value::Number(left), Value::Number(right)) => Ok(Value::create_boolean(*left > *right)),
+      (TokenEnum::Less, Value::Number(left), Value::Number(right)) => Ok(Value::create_boolean(*left < *right)),
       (TokenEnum::GreaterEqual, Value::Number(left), Value::Number(right)) => {
-        return Value::create_boolean(*left >= *right);
+        Ok(Value::create_boolean(*left >= *right))
       }
-      (TokenEnum::LessEqual, Value::Number(left), Value::Number(right)) => {
-        return Value::create_boolean(*left <= *right);
-      }
-      _ => unreachable!(),
+      (TokenEnum::LessEqual, Value::Number(left), Value::Number(right)) => Ok(Value::create_boolean(*left <= *right)),
+      _ => Err(RuntimeError::type_error(operator, "Operands must be numbers.")),
     }
   }
 
-  fn visit_unary_expression(&mut self, unary_expression: &ast::UnaryExpression) -> Value {
-    let right = self.evaluate(&unary_expression.right);
+  fn visit_unary_expression(&mut self, unary_expression: &ast::UnaryExpression) -> EvaluatorResult {
+    let right = self.evaluate(&unary_expression.right)?;
+    let operator = &unary_expression.operator;
 
-    if unary_expression.operator.kind == TokenEnum::Minus && !right.is_number() {
-      self.runtime_error(&*unary_expression.operator, "Unsupported operator, expected a number.");
+    match &operator.kind {
+      TokenEnum::Bang => Ok(Value::create_boolean(!self.is_truthy(&right))),
+      TokenEnum::Minus if right.is_number() => Ok(Value::create_number(-right.as_number())),
+      _ => Err(RuntimeError::type_error(operator, "Operand must be a number.")),
     }
-    match &unary_expression.operator.kind {
-      TokenEnum::Bang => Value::create_boolean(!self.is_truthy(&right)),
-      TokenEnum::Minus => Value::create_number(-right.as_number()),
-      _ => unreachable!(),
+  }
+
+  fn visit_logical_expression(&mut self, logical_expression: &ast::LogicalExpression) -> EvaluatorResult {
+    let left = self.evaluate(&logical_expression.left)?;
+    let left_is_truthy = self.is_truthy(&left);
+    match logical_expression.operator.kind {
+      TokenEnum::Or if left_is_truthy => Ok(left),
+      TokenEnum::And if !left_is_truthy => Ok(left),
+      _ => self.evaluate(&logical_expression.right),
     }
   }
-  fn visit_grouping_expression(&mut self, grouping_expression: &ast::GroupingExpression) -> Value {
+
+  fn visit_call_expression(&mut self, call_expression: &ast::CallExpression) -> EvaluatorResult {
+    let callee = self.evaluate(&call_expression.callee)?;
+    let mut arguments = Vec::with_capacity(call_expression.arguments.len());
+    for argument in &call_expression.arguments {
+      arguments.push(self.evaluate(argument)?);
+    }
+    self.call_value(&callee, arguments, &call_expression.paren)
+  }
+
+  fn visit_get_expression(&mut self, get_expression: &ast::GetExpression) -> EvaluatorResult {
+    let object = self.evaluate(&get_expression.object)?;
+    let name = &get_expression.name;
+    match &object {
+      Value::Instance(instance) => {
+        Instance::get(instance, &name.lexeme).ok_or_else(|| RuntimeError::undefined_property(name))
+      }
+      _ => Err(RuntimeError::not_an_instance(name, "Only instances have properties.")),
+    }
+  }
+
+  fn visit_set_expression(&mut self, set_expression: &ast::SetExpression) -> EvaluatorResult {
+    // like the vm: both sides are evaluated before the object is checked.
+    let object = self.evaluate(&set_expression.object)?;
+    let value = self.evaluate(&set_expression.value)?;
+    let name = &set_expression.name;
+    let Value::Instance(instance) = &object else {
+      return Err(RuntimeError::not_an_instance(name, "Only instances have fields."));
+    };
+    instance.borrow_mut().set(&name.lexeme, value.clone());
+    Ok(value)
+  }
+
+  fn visit_this_expression(&mut self, this_expression: &ast::ThisExpression) -> EvaluatorResult {
+    self.look_up_variable(&this_expression.keyword, this_expression.id)
+  }
+
+  // finds the method on the superclass and binds it to the current `this`.
+  fn visit_super_expression(&mut self, super_expression: &ast::SuperExpression) -> EvaluatorResult {
+    let keyword = &super_expression.keyword;
+    let Some(&distance) = self.locals.get(&super_expression.id) else {
+      return Err(RuntimeError::undefined_variable(keyword));
+    };
+    let Some(Value::Class(superclass)) = Environment::get_at(&self.environment, distance, &keyword.lexeme) else {
+      return Err(RuntimeError::undefined_variable(keyword));
+    };
+    // the scope binding `this` is always the one just inside the scope binding `super`.
+    let this =
+      Environment::get_at(&self.environment, distance - 1, &Symbol::intern("this")).unwrap_or_else(Value::create_nil);
+    let method = &super_expression.method;
+    match superclass.find_method(&method.lexeme) {
+      Some(function) => Ok(Value::create_function(function.bind(this))),
+      None => Err(RuntimeError::undefined_property(method)),
+    }
+  }
+
+  fn visit_grouping_expression(&mut self, grouping_expression: &ast::GroupingExpression) -> EvaluatorResult {
     self.evaluate(&grouping_expression.expression)
   }
 
-  fn visit_literal(&mut self, literal: &ast::Literal) -> Value {
-    match literal {
+  fn visit_variable_expression(&mut self, variable_expression: &ast::VariableExpression) -> EvaluatorResult {
+    self.look_up_variable(&variable_expression.name, variable_expression.id)
+  }
+
+  fn visit_assignment_expression(&mut self, assignment_expression: &ast::AssignmentExpression) -> EvaluatorResult {
+    let value = self.evaluate(&assignment_expression.value)?;
+    let name = &assignment_expression.name;
+    match self.locals.get(&assignment_expression.id) {
+      Some(distance) => Environment::assign_at(&self.environment, *distance, &name.lexeme, value.clone()),
+      None => {
+        if !self.globals.borrow_mut().assign(&name.lexeme, value.clone()) {
+          return Err(RuntimeError::undefined_variable(name));
+        }
+      }
+    }
+    Ok(value)
+  }
+
+  fn visit_literal(&mut self, literal: &ast::Literal) -> EvaluatorResult {
+    let value = match literal {
       ast::Literal::StringLiteral(string_literal) => Value::create_string(string_literal.clone()),
       ast::Literal::NumberLiteral(number_literal) => Value::create_number(*number_literal),
       ast::Literal::BooleanLiteral(boolean_literal) => Value::create_boolean(*boolean_literal),
       ast::Literal::NullLiteral => Value::create_nil(),
-    }
+    };
+    Ok(value)
   }
 
-  fn visit_expression(&mut self, expression: &ast::Expression) -> Value {
+  fn visit_expression(&mut self, expression: &ast::Expression) -> EvaluatorResult {
     self.evaluate(expression)
   }
 }
 
 impl Evaluator {
   pub fn new(name: &str) -> Self {
-    Evaluator { error_handler: ErrorHandler::new(name) }
+    let globals = Rc::new(RefCell::new(Environment::new()));
+    let clock = NativeFunction::new(0, clock);
+    globals
+      .borrow_mut()
+      .define(&Symbol::intern("clock"), Value::create_native_function(clock));
+    Evaluator {
+      error_handler: ErrorHandler::new(name),
+      truthiness: Truthiness::default(),
+      output: Output::Stdout,
+      environment: Rc::clone(&globals),
+      globals,
+      locals: HashMap::new(),
+      return_value: None,
+      call_depth: 0,
+    }
+  }
+
+  pub fn interpret(&mut self, program: &ast::Program) -> EvaluatorResult {
+    program.accept(self)
+  }
+
+  pub fn execute(&mut self, statement: &ast::Statement) -> EvaluatorResult {
+    statement.accept(self)
   }
-  pub fn evaluate(&mut self, ast: &ast::Expression) -> Value {
+
+  pub fn evaluate(&mut self, ast: &ast::Expression) -> EvaluatorResult {
     ast.accept(self)
   }
 
-  pub fn is_suport_operator(&self, operator: &TokenEnum, value: &Value) -> bool {
-    match (operator, value) {
-      (TokenEnum::Plus, Value::Number(_)) => true,
-      (TokenEnum::Plus, Value::String(_)) => true,
-      (TokenEnum::Minus, Value::Number(_)) => true,
-      (TokenEnum::Star, Value::Number(_)) => true,
-      (TokenEnum::Slash, Value::Number(_)) => true,
-      (TokenEnum::Greater, Value::Number(_)) => true,
-      (TokenEnum::Less, Value::Number(_)) => true,
-      (TokenEnum::GreaterEqual, Value::Number(_)) => true,
-      (TokenEnum::LessEqual, Value::Number(_)) => true,
-      _ => false,
+  // runs the statements in the given scope, restoring the current one afterwards (even on error).
+  // stops early once a `return` has been executed.
+  pub fn execute_block(&mut self, statements: &[ast::Statement], environment: Environment) -> Result<(), RuntimeError> {
+    let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+    let mut result = Ok(());
+    for statement in statements {
+      result = self.execute(statement).map(|_| ());
+      if result.is_err() || self.return_value.is_some() {
+        break;
+      }
     }
+    self.environment = previous;
+    result
   }
 
-  pub fn is_equal(&self, left: &Value, right: &Value) -> bool {
-    match (left, right) {
-      (Value::Number(left), Value::Number(right)) => left == right,
-      (Value::String(left), Value::String(right)) => left == right,
-      (Value::Boolean(left), Value::Boolean(right)) => left == right,
-      (Value::Nil, Value::Nil) => true,
-      _ => false,
+  // `paren` is the closing parenthesis of the call, where call errors are reported.
+  pub fn call_value(&mut self, callee: &Value, arguments: Vec<Value>, paren: &ast::tokens::Token) -> EvaluatorResult {
+    match callee {
+      Value::Function(function) => {
+        if arguments.len() != function.arity() {
+          return Err(RuntimeError::arity_mismatch(paren, function.arity(), arguments.len()));
+        }
+        self.call_function(function, arguments, paren)
+      }
+      Value::NativeFunction(native) => {
+        if arguments.len() != native.arity {
+          return Err(RuntimeError::arity_mismatch(paren, native.arity, arguments.len()));
+        }
+        Ok((native.function)(&arguments))
+      }
+      Value::Class(class) => {
+        if arguments.len() != class.arity() {
+          return Err(RuntimeError::arity_mismatch(paren, class.arity(), arguments.len()));
+        }
+        let instance = Value::create_instance(Instance::new(Rc::clone(class)));
+        if let Some(initializer) = class.find_method(&Symbol::intern("init")) {
+          self.call_function(&initializer.bind(instance.clone()), arguments, paren)?;
+        }
+        Ok(instance)
+      }
+      _ => Err(RuntimeError::not_callable(paren)),
     }
   }
 
-  pub fn check_suport_binary_operator(&self, operator: &TokenEnum, value: &Value) -> bool {
-    match (operator, value) {
-      (TokenEnum::Plus, Value::Number(_)) => true,
-      (TokenEnum::Plus, Value::String(_)) => true,
-      (TokenEnum::Minus, Value::Number(_)) => true,
-      (TokenEnum::Star, Value::Number(_)) => true,
-      (TokenEnum::Slash, Value::Number(_)) => true,
-      (TokenEnum::Greater, Value::Number(_)) => true,
-      (TokenEnum::Less, Value::Number(_)) => true,
-      (TokenEnum::GreaterEqual, Value::Number(_)) => true,
-      (TokenEnum::LessEqual, Value::Number(_)) => true,
-      _ => false,
+  fn call_function(
+    &mut self,
+    function: &Function,
+    arguments: Vec<Value>,
+    paren: &ast::tokens::Token,
+  ) -> EvaluatorResult {
+    if self.call_depth >= MAX_CALL_DEPTH {
+      return Err(RuntimeError::stack_overflow(paren));
     }
+    let mut environment = Environment::new_enclosed(Rc::clone(&function.closure));
+    for (param, argument) in function.declaration.params.iter().zip(arguments) {
+      environment.define(&param.lexeme, argument);
+    }
+    self.call_depth += 1;
+    let result = self.execute_block(&function.declaration.body, environment);
+    self.call_depth -= 1;
+    let return_value = self.return_value.take();
+    result?;
+    if function.is_initializer {
+      let this = function.closure.borrow().get(&Symbol::intern("this"));
+      return Ok(this.unwrap_or_else(Value::create_nil));
+    }
+    Ok(return_value.unwrap_or_else(Value::create_nil))
   }
 
-  pub fn check_suport_unary_operator(&self, operator: &TokenEnum, value: &Value) -> bool {
-    match (operator, value) {
-      (TokenEnum::Bang, Value::Boolean(_)) => true,
-      (TokenEnum::Minus, Value::Number(_)) => true,
-      (TokenEnum::Bang, Value::Number(_)) => true,
-      _ => false,
-    }
+  // takes the scope distances computed by the resolver; the REPL adds more on every line.
+  pub fn add_locals(&mut self, locals: HashMap<ast::NodeId, usize>) {
+    self.locals.extend(locals);
   }
 
-  pub fn is_number(&self, value: &Value) -> bool {
-    match value {
-      Value::Number(_) => true,
-      _ => false,
-    }
+  fn look_up_variable(&self, name: &ast::tokens::Token, id: ast::NodeId) -> EvaluatorResult {
+    let value = match self.locals.get(&id) {
+      Some(distance) => Environment::get_at(&self.environment, *distance, &name.lexeme),
+      None => self.globals.borrow().get(&name.lexeme),
+    };
+    value.ok_or_else(|| RuntimeError::undefined_variable(name))
   }
-  pub fn is_string(&self, value: &Value) -> bool {
-    match value {
-      Value::String(_) => true,
-      _ => false,
-    }
+
+  // strings are interned, so this compares them by identity rather than by text.
+  pub fn is_equal(&self, left: &Value, right: &Value) -> bool {
+    left.equals(right)
   }
 
-  fn is_truthy(&self, value: &Value) -> bool {
-    match value {
-      Value::Nil => false,
-      Value::Boolean(boolean) => *boolean,
-      Value::Number(number) => {
-        if *number == 0.0 {
-          return false;
-        }
-        true
-      }
-      Value::String(string) => string.len() > 0,
-    }
+  pub fn is_truthy(&self, value: &Value) -> bool {
+    value.is_truthy_with(self.truthiness)
   }
-  pub fn runtime_error(&mut self, token: &Token, message: &str) {
-    self.error_handler.error(token.line, message);
+
+  // records a runtime error so it reaches the same diagnostics as scan/parse errors.
+  pub fn report_runtime_error(&mut self, error: &RuntimeError) {
+    self.error_handler.push(error.to_diagnostic());
   }
 }
@@ -0,0 +1,60 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::environment::Environment;
+use crate::ast;
+use crate::shared::symbol::Symbol;
+use crate::values::Value;
+
+// a user-defined function together with the scope it was declared in.
+pub struct Function {
+  pub declaration: Rc<ast::FunctionStatement>,
+  pub closure: Rc<RefCell<Environment>>,
+  // an `init` method always returns the instance it was bound to.
+  pub is_initializer: bool,
+}
+
+impl Function {
+  pub fn new(declaration: Rc<ast::FunctionStatement>, closure: Rc<RefCell<Environment>>, is_initializer: bool) -> Self {
+    Function { declaration, closure, is_initializer }
+  }
+
+  // a copy of this method whose scope defines `this` as the given instance.
+  pub fn bind(&self, instance: Value) -> Function {
+    let mut environment = Environment::new_enclosed(Rc::clone(&self.closure));
+    environment.define(&Symbol::intern("this"), instance);
+    Function::new(
+      Rc::clone(&self.declaration),
+      Rc::new(RefCell::new(environment)),
+      self.is_initializer,
+    )
+  }
+
+  pub fn name(&self) -> &str {
+    &self.declaration.name.lexeme
+  }
+
+  pub fn arity(&self) -> usize {
+    self.declaration.params.len()
+  }
+}
+
+// a function implemented in rust and exposed to lox as a global.
+pub struct NativeFunction {
+  pub arity: usize,
+  pub function: fn(&[Value]) -> Value,
+}
+
+impl NativeFunction {
+  pub fn new(arity: usize, function: fn(&[Value]) -> Value) -> Self {
+    NativeFunction { arity, function }
+  }
+}
+
+// seconds since the unix epoch, used by benchmarks to time themselves.
+pub fn clock(_arguments: &[Value]) -> Value {
+  let elapsed = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .unwrap_or_default();
+  Value::create_number(elapsed.as_secs_f64())
+}
@@ -1,2 +1,10 @@
+mod class;
+mod environment;
+#[allow(clippy::module_inception)]
 mod evaluator;
+mod function;
+mod runtime_error;
+pub use class::*;
 pub use evaluator::*;
+pub use function::*;
+pub use runtime_error::*;
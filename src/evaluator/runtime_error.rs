@@ -0,0 +1,85 @@
+use std::fmt::{Display, Formatter};
+
+use crate::ast::tokens::Token;
+use crate::diagnostics::error_handler::ErrorHandler;
+use crate::diagnostics::Diagnostic;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeErrorKind {
+  TypeError,         // an operand has the wrong type for its operator
+  UndefinedVariable, // read or assignment of a name that was never declared
+  NotCallable,       // a call whose callee is not a function
+  ArityMismatch,     // a call with the wrong number of arguments
+  StackOverflow,     // calls nested deeper than the evaluator allows
+  NotAnInstance,     // a property access on something that is not an instance
+  UndefinedProperty, // a property that is neither a field nor a method
+  InvalidSuperclass, // a class inheriting from something that is not a class
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+  pub token: Token, // where the error happened, used for the line in reports
+  pub message: String,
+  #[allow(dead_code)] // for callers that branch on the category rather than the message
+  pub kind: RuntimeErrorKind,
+}
+
+impl RuntimeError {
+  pub fn new(token: &Token, kind: RuntimeErrorKind, message: &str) -> Self {
+    RuntimeError { token: token.clone(), message: message.to_owned(), kind }
+  }
+
+  pub fn to_diagnostic(&self) -> Diagnostic {
+    ErrorHandler::token_diagnostic(&self.token, &self.message)
+  }
+
+  pub fn type_error(token: &Token, message: &str) -> Self {
+    RuntimeError::new(token, RuntimeErrorKind::TypeError, message)
+  }
+
+  pub fn undefined_variable(token: &Token) -> Self {
+    let message = format!("Undefined variable '{}'.", token.lexeme);
+    RuntimeError::new(token, RuntimeErrorKind::UndefinedVariable, &message)
+  }
+
+  pub fn not_callable(token: &Token) -> Self {
+    RuntimeError::new(
+      token,
+      RuntimeErrorKind::NotCallable,
+      "Can only call functions and classes.",
+    )
+  }
+
+  pub fn arity_mismatch(token: &Token, expected: usize, got: usize) -> Self {
+    let message = format!("Expected {} arguments but got {}.", expected, got);
+    RuntimeError::new(token, RuntimeErrorKind::ArityMismatch, &message)
+  }
+
+  // `message` distinguishes reads ("properties") from writes ("fields").
+  pub fn not_an_instance(token: &Token, message: &str) -> Self {
+    RuntimeError::new(token, RuntimeErrorKind::NotAnInstance, message)
+  }
+
+  pub fn undefined_property(token: &Token) -> Self {
+    let message = format!("Undefined property '{}'.", token.lexeme);
+    RuntimeError::new(token, RuntimeErrorKind::UndefinedProperty, &message)
+  }
+
+  pub fn invalid_superclass(token: &Token) -> Self {
+    RuntimeError::new(
+      token,
+      RuntimeErrorKind::InvalidSuperclass,
+      "Superclass must be a class.",
+    )
+  }
+
+  pub fn stack_overflow(token: &Token) -> Self {
+    RuntimeError::new(token, RuntimeErrorKind::StackOverflow, "Stack overflow.")
+  }
+}
+
+impl Display for RuntimeError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}\n[line {}]", self.message, self.token.line)
+  }
+}
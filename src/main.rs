@@ -1,69 +1,315 @@
-use std::fs::File;
-use std::io::Read;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
+use std::thread;
 
 mod ast;
 mod cli;
 mod diagnostics;
 mod evaluator;
 mod parser;
+mod pipeline;
 mod repl;
+mod resolver;
 mod scanner;
 mod shared;
+mod test_suite;
 mod values;
 mod visitor;
 mod vm;
 
 use cli::command_line;
-use parser::Parser;
+use diagnostics::error_handler::ErrorHandler;
+use diagnostics::{Diagnostic, DiagnosticKind};
+use pipeline::Outcome;
 use repl::prompt;
-use scanner::Scanner;
-use visitor::print_visitor::PrintVisitor;
+use shared::constants::{
+  COMPILE_ERROR_EXIT_CODE, ERROR_EXIT_CODE, INTERPRETER_STACK_SIZE, IO_ERROR_EXIT_CODE, SUCCESS_EXIT_CODE,
+};
+use shared::options::RunOptions;
+use shared::output::Output;
+use test_suite::run_test_suite;
+use vm::{FunctionProto, VmStats};
 
 struct LoxFile {
   pathname: String,
   content: String,
 }
 
-fn read_file(file_name: &str) -> LoxFile {
-  let mut file = File::open(file_name).unwrap();
+// `-` reads the program from stdin.
+fn read_file(file_name: &str) -> io::Result<LoxFile> {
   let mut content = String::new();
-  file.read_to_string(&mut content).unwrap();
+  if file_name == "-" {
+    io::stdin().read_to_string(&mut content)?;
+    return Ok(LoxFile { pathname: String::from("<stdin>"), content });
+  }
+  let mut file = File::open(file_name)?;
+  file.read_to_string(&mut content)?;
   let pathname = file_name.to_string();
-  return LoxFile { pathname, content };
+  Ok(LoxFile { pathname, content })
 }
 
-pub fn execute(raw: &str, path_name: &str) {
-  let mut scanner = Scanner::new(raw.to_string(), path_name);
-  let mut parser = Parser::new(&mut scanner);
-  let ast = parser.parse();
-  let mut visitor = PrintVisitor;
-  let result = ast.accept(&mut visitor);
-  println!("{}", result);
+fn has_extension(file_name: &str, extension: &str) -> bool {
+  Path::new(file_name)
+    .extension()
+    .is_some_and(|actual| actual == extension)
+}
+
+// prints what a run reported and returns the process exit code.
+fn report(outcome: &Outcome, error_handler: &ErrorHandler, options: &RunOptions) -> i32 {
+  for error in outcome.compile_errors.iter().chain(&outcome.runtime_error) {
+    error_handler.print(error);
+  }
+  if let (true, Some(stats)) = (options.stats, &outcome.stats) {
+    print_stats(stats);
+  }
+  outcome.exit_code()
+}
+
+// `--stats`: how often each kind of inline cache hit, and what the heap did, printed to stderr after a vm run.
+fn print_stats(stats: &VmStats) {
+  let caches = [
+    ("get property", stats.caches.get_property),
+    ("set property", stats.caches.set_property),
+    ("invoke", stats.caches.invoke),
+  ];
+  eprintln!("== stats ==");
+  for (name, counters) in caches {
+    let hit_rate = match counters.hit_rate() {
+      Some(rate) => format!("{:.1}%", rate * 100.0),
+      None => String::from("-"),
+    };
+    eprintln!(
+      "{:<14} {:>10} hits {:>10} misses {:>7} hit rate",
+      name, counters.hits, counters.misses, hit_rate
+    );
+  }
+  eprintln!("{:<14} {:>10}", "shapes", stats.shapes);
+  eprintln!(
+    "{:<14} {:>10} collections {:>10} objects freed {:>10} objects live",
+    "gc", stats.gc.collections, stats.gc.objects_freed, stats.live_objects
+  );
 }
 // =====================
 // run file(compile) file output result
 //
-fn run(file_name: &str) {
-  let file = read_file(file_name);
-  execute(&file.content, file_name)
+fn run(file_name: &str, options: &RunOptions) {
+  if has_extension(file_name, vm::BYTECODE_EXTENSION) {
+    let exit_code = run_bytecode_file(file_name, options);
+    if exit_code != SUCCESS_EXIT_CODE {
+      process::exit(exit_code);
+    }
+    return;
+  }
+  let assembly = has_extension(file_name, vm::ASSEMBLY_EXTENSION);
+  let file = match read_file(file_name) {
+    Ok(file) => file,
+    Err(error) => {
+      eprintln!("error: could not read '{}': {}", file_name, error);
+      process::exit(IO_ERROR_EXIT_CODE);
+    }
+  };
+  let outcome = match assembly {
+    true => pipeline::run_assembly(&file.content, options, Output::Stdout),
+    false => pipeline::run_source(&file.content, &file.pathname, options, Output::Stdout),
+  };
+  let exit_code = report(
+    &outcome,
+    &ErrorHandler::with_source(&file.pathname, &file.content),
+    options,
+  );
+  if exit_code != SUCCESS_EXIT_CODE {
+    process::exit(exit_code);
+  }
+}
+
+// loads a `.hexc` file straight into the vm, whichever backend was asked for.
+fn run_bytecode_file(file_name: &str, options: &RunOptions) -> i32 {
+  let bytes = match fs::read(file_name) {
+    Ok(bytes) => bytes,
+    Err(error) => {
+      eprintln!("error: could not read '{}': {}", file_name, error);
+      return IO_ERROR_EXIT_CODE;
+    }
+  };
+  let error_handler = ErrorHandler::new(file_name);
+  let file = match vm::decode(&bytes) {
+    Ok(file) => file,
+    Err(error) => {
+      error_handler.print(&error.to_diagnostic());
+      return COMPILE_ERROR_EXIT_CODE;
+    }
+  };
+  if let Err(errors) = vm::verify(&file.script) {
+    for error in &errors {
+      error_handler.print(error);
+    }
+    return COMPILE_ERROR_EXIT_CODE;
+  }
+  let source_path = Path::new(file_name).with_extension("hex");
+  if let Ok(source) = fs::read_to_string(&source_path) {
+    if vm::source_hash(&source) != file.source_hash {
+      let warning = Diagnostic::new(
+        DiagnosticKind::Warning,
+        "Bytecode is out of date with its source.",
+        0,
+        "",
+      )
+      .with_note(&format!("{} changed since it was compiled", source_path.display()));
+      error_handler.print(&warning);
+    }
+  }
+  let outcome = pipeline::run_script(file.script, options, Output::Stdout);
+  report(&outcome, &error_handler, options)
 }
 
 // =====================
 // compile file output bytecode
 //
-fn compile(file: &str) {}
+// returns the compiled script, or the exit code once every error has been printed.
+fn compile_source(raw: &str, path_name: &str) -> Result<Rc<FunctionProto>, i32> {
+  pipeline::compile_source(raw, path_name).map_err(|errors| print_errors(&errors, raw, path_name))
+}
+
+fn print_errors(errors: &[Diagnostic], raw: &str, path_name: &str) -> i32 {
+  let error_handler = ErrorHandler::with_source(path_name, raw);
+  for error in errors {
+    error_handler.print(error);
+  }
+  COMPILE_ERROR_EXIT_CODE
+}
+
+// writes `file.hexc` next to `file.hex`; `-` compiles stdin to stdout.
+fn compile(file_name: &str) {
+  let file = match read_file(file_name) {
+    Ok(file) => file,
+    Err(error) => {
+      eprintln!("error: could not read '{}': {}", file_name, error);
+      process::exit(IO_ERROR_EXIT_CODE);
+    }
+  };
+  let script = match compile_source(&file.content, &file.pathname) {
+    Ok(script) => script,
+    Err(exit_code) => process::exit(exit_code),
+  };
+  write_bytecode(file_name, &script, &file.content);
+}
+
+// =====================
+// asm file output bytecode
+//
+fn assemble_source(raw: &str, path_name: &str) -> Result<Rc<FunctionProto>, i32> {
+  pipeline::assemble_source(raw).map_err(|errors| print_errors(&errors, raw, path_name))
+}
+
+// writes `file.hexc` next to `file.hexasm`; `-` assembles stdin to stdout.
+fn asm(file_name: &str) {
+  let file = match read_file(file_name) {
+    Ok(file) => file,
+    Err(error) => {
+      eprintln!("error: could not read '{}': {}", file_name, error);
+      process::exit(IO_ERROR_EXIT_CODE);
+    }
+  };
+  let script = match assemble_source(&file.content, &file.pathname) {
+    Ok(script) => script,
+    Err(exit_code) => process::exit(exit_code),
+  };
+  write_bytecode(file_name, &script, &file.content);
+}
+
+fn write_bytecode(file_name: &str, script: &FunctionProto, source: &str) {
+  let bytes = vm::encode(script, source);
+  let result = match file_name {
+    "-" => io::stdout().write_all(&bytes),
+    _ => {
+      let output = Path::new(file_name).with_extension(vm::BYTECODE_EXTENSION);
+      fs::write(&output, &bytes)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", output.display(), error)))
+    }
+  };
+  if let Err(error) = result {
+    eprintln!("error: could not write bytecode: {}", error);
+    process::exit(IO_ERROR_EXIT_CODE);
+  }
+}
+
+// =====================
+// disasm file output listing
+//
+// a `.hexc` file is decoded as is; anything else is compiled first.
+fn disasm(file_name: &str) {
+  let script = match has_extension(file_name, vm::BYTECODE_EXTENSION) {
+    true => {
+      let bytes = match fs::read(file_name) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+          eprintln!("error: could not read '{}': {}", file_name, error);
+          process::exit(IO_ERROR_EXIT_CODE);
+        }
+      };
+      match vm::decode(&bytes) {
+        Ok(file) => file.script,
+        Err(error) => {
+          ErrorHandler::new(file_name).print(&error.to_diagnostic());
+          process::exit(COMPILE_ERROR_EXIT_CODE);
+        }
+      }
+    }
+    false => {
+      let file = match read_file(file_name) {
+        Ok(file) => file,
+        Err(error) => {
+          eprintln!("error: could not read '{}': {}", file_name, error);
+          process::exit(IO_ERROR_EXIT_CODE);
+        }
+      };
+      match compile_source(&file.content, &file.pathname) {
+        Ok(script) => script,
+        Err(exit_code) => process::exit(exit_code),
+      }
+    }
+  };
+  print!("{}", vm::disassemble(&script));
+}
 
+// runs on a thread with a larger stack so deep lox recursion reports "Stack overflow." instead of crashing.
 fn main() {
+  let interpreter = thread::Builder::new()
+    .stack_size(INTERPRETER_STACK_SIZE)
+    .spawn(lox_main)
+    .expect("failed to spawn the interpreter thread");
+  if interpreter.join().is_err() {
+    process::exit(ERROR_EXIT_CODE);
+  }
+}
+
+fn lox_main() {
   let matches = command_line();
+  let options = RunOptions::from_matches(&matches);
   match matches.subcommand() {
     Some(("run", sub_matches)) => {
       let file = sub_matches.get_one::<String>("file").unwrap();
-      run(file);
+      run(file, &options);
     }
     Some(("compile", sub_matches)) => {
       let file = sub_matches.get_one::<String>("file").unwrap();
       compile(file);
     }
-    _ => prompt(),
+    Some(("asm", sub_matches)) => {
+      let file = sub_matches.get_one::<String>("file").unwrap();
+      asm(file);
+    }
+    Some(("disasm", sub_matches)) => {
+      let file = sub_matches.get_one::<String>("file").unwrap();
+      disasm(file);
+    }
+    Some(("test-suite", sub_matches)) => {
+      let directory = sub_matches.get_one::<String>("directory").unwrap();
+      process::exit(run_test_suite(directory, &options));
+    }
+    _ => prompt(&options),
   }
 }
@@ -1,45 +1,374 @@
-use std::process;
+use std::rc::Rc;
 
 use crate::ast;
 use crate::ast::tokens::{Token, TokenEnum};
+use crate::diagnostics::error_handler::ErrorHandler;
+use crate::diagnostics::Diagnostic;
 use crate::scanner::Scanner;
-use crate::shared::constants::ERROR_EXIT_CODE;
+
+// marker for a syntax error that has already been reported; used to unwind to the next statement.
+#[derive(Debug)]
+pub struct ParseError;
+
+type ParseResult<T> = Result<T, ParseError>;
+
+// the bytecode encodes argument counts in a single byte.
+pub const MAX_ARGUMENTS: usize = 255;
+
+// every later pass walks expressions recursively too, so the parser stops before any of them could overflow.
+const MAX_EXPRESSION_DEPTH: usize = 256;
+// the same for blocks, function bodies and the bodies of if, while and for.
+const MAX_STATEMENT_DEPTH: usize = 256;
 
 pub struct Parser<'a> {
   current_token_pointer: usize,
+  expression_depth: usize,
+  statement_depth: usize,
+  gave_up: bool, // set once statements nest too deeply; the rest of the input is skipped
   scanner: &'a mut Scanner,
 }
 
+// the parser and the scanner spell out every `return`.
+#[allow(clippy::needless_return)]
 impl<'a> Parser<'a> {
-  pub fn new(scanner: &mut Scanner) -> Parser {
+  pub fn new(scanner: &mut Scanner) -> Parser<'_> {
     scanner.scan_tokens();
-    Parser { current_token_pointer: 0, scanner }
+    Parser { current_token_pointer: 0, expression_depth: 0, statement_depth: 0, gave_up: false, scanner }
+  }
+
+  // program        → declaration* EOF ;
+  // returns every statement that parsed cleanly, plus every scan/syntax error found along the way.
+  pub fn parse(&mut self) -> (ast::Program, Vec<Diagnostic>) {
+    let mut statements = vec![];
+    while !self.is_at_end() {
+      if let Some(statement) = self.parse_declaration() {
+        statements.push(statement);
+      }
+    }
+    // scan errors are all collected before parsing starts; report everything in source order.
+    let mut errors = self.scanner.error_handler.take_diagnostics();
+    errors.sort_by_key(|error| error.line);
+    (ast::Program::new(statements), errors)
+  }
+
+  // declaration    → classDecl | funDecl | varDecl | statement ;
+  pub fn parse_declaration(&mut self) -> Option<ast::Statement> {
+    let statement = if self.is_match(TokenEnum::Class) {
+      self.consume();
+      self.parse_class_declaration()
+    } else if self.is_match(TokenEnum::Fun) {
+      self.consume();
+      self.parse_function_declaration()
+    } else if self.is_match(TokenEnum::Var) {
+      self.consume();
+      self.parse_variable_declaration()
+    } else {
+      self.nested_statement(Self::parse_statement)
+    };
+    match statement {
+      Ok(statement) => Some(statement),
+      Err(ParseError) => {
+        self.synchronize();
+        None
+      }
+    }
+  }
+
+  // classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}" ;
+  pub fn parse_class_declaration(&mut self) -> ParseResult<ast::Statement> {
+    let name = self
+      .consume_expected(TokenEnum::Identifier, "Expected class name.")?
+      .clone();
+    let mut superclass = None;
+    if self.is_match(TokenEnum::Less) {
+      self.consume();
+      let superclass_name = self
+        .consume_expected(TokenEnum::Identifier, "Expected superclass name.")?
+        .clone();
+      superclass = Some(ast::VariableExpression::new(Box::new(superclass_name)));
+    }
+    self.consume_expected(TokenEnum::LeftBrace, "Expected '{' before class body.")?;
+    let mut methods = vec![];
+    while !self.is_match(TokenEnum::RightBrace) && !self.is_at_end() {
+      methods.push(self.parse_function("method")?);
+    }
+    self.consume_expected(TokenEnum::RightBrace, "Expected '}' after class body.")?;
+    let class_statement = ast::ClassStatement::new(Box::new(name), superclass, methods);
+    return Ok(ast::Statement::ClassStatement(class_statement));
+  }
+
+  // funDecl        → "fun" function ;
+  pub fn parse_function_declaration(&mut self) -> ParseResult<ast::Statement> {
+    let function_statement = self.parse_function("function")?;
+    return Ok(ast::Statement::FunctionStatement(function_statement));
+  }
+
+  // function       → IDENTIFIER "(" parameters? ")" block ;
+  // parameters     → IDENTIFIER ( "," IDENTIFIER )* ;
+  pub fn parse_function(&mut self, kind: &str) -> ParseResult<Rc<ast::FunctionStatement>> {
+    let name = self
+      .consume_expected(TokenEnum::Identifier, &format!("Expected {} name.", kind))?
+      .clone();
+    self.consume_expected(TokenEnum::LeftParen, &format!("Expected '(' after {} name.", kind))?;
+    let mut params = vec![];
+    if !self.is_match(TokenEnum::RightParen) {
+      loop {
+        if params.len() >= MAX_ARGUMENTS {
+          let token = self.peek().clone();
+          self.error(token, &format!("Can't have more than {} parameters.", MAX_ARGUMENTS));
+        }
+        params.push(
+          self
+            .consume_expected(TokenEnum::Identifier, "Expected parameter name.")?
+            .clone(),
+        );
+        if !self.is_match(TokenEnum::Comma) {
+          break;
+        }
+        self.consume();
+      }
+    }
+    self.consume_expected(TokenEnum::RightParen, "Expected ')' after parameters.")?;
+    self.consume_expected(TokenEnum::LeftBrace, &format!("Expected '{{' before {} body.", kind))?;
+    let body = self.nested_statement(Self::parse_block)?;
+    return Ok(Rc::new(ast::FunctionStatement::new(Box::new(name), params, body)));
+  }
+
+  // varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+  pub fn parse_variable_declaration(&mut self) -> ParseResult<ast::Statement> {
+    self.consume_expected(TokenEnum::Identifier, "Expected variable name.")?;
+    let name = self.get_previous_token().clone();
+    let mut initializer = None;
+    if self.is_match(TokenEnum::Equal) {
+      self.consume();
+      initializer = Some(self.parse_expression()?);
+    }
+    self.consume_expected(TokenEnum::Semicolon, "Expected ';' after variable declaration.")?;
+    let variable_statement = ast::VariableStatement::new(Box::new(name), initializer);
+    return Ok(ast::Statement::VariableStatement(variable_statement));
+  }
+
+  // statement      → exprStmt | forStmt | ifStmt | printStmt | returnStmt | whileStmt | block ;
+  pub fn parse_statement(&mut self) -> ParseResult<ast::Statement> {
+    if self.is_match(TokenEnum::Return) {
+      self.consume();
+      return self.parse_return_statement();
+    }
+    if self.is_match(TokenEnum::For) {
+      self.consume();
+      return self.parse_for_statement();
+    }
+    if self.is_match(TokenEnum::If) {
+      self.consume();
+      return self.parse_if_statement();
+    }
+    if self.is_match(TokenEnum::While) {
+      self.consume();
+      return self.parse_while_statement();
+    }
+    if self.is_match(TokenEnum::Print) {
+      self.consume();
+      return self.parse_print_statement();
+    }
+    if self.is_match(TokenEnum::LeftBrace) {
+      self.consume();
+      let block_statement = ast::BlockStatement::new(self.parse_block()?);
+      return Ok(ast::Statement::BlockStatement(block_statement));
+    }
+    return self.parse_expression_statement();
+  }
+
+  /*
+  forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
+                   expression? ";"
+                   expression? ")" statement ;
+  desugared to `{ initializer; while (condition) { body; increment; } }`.
+  */
+  pub fn parse_for_statement(&mut self) -> ParseResult<ast::Statement> {
+    self.consume_expected(TokenEnum::LeftParen, "Expected '(' after 'for'.")?;
+    let initializer = if self.is_match(TokenEnum::Semicolon) {
+      self.consume();
+      None
+    } else if self.is_match(TokenEnum::Var) {
+      self.consume();
+      Some(self.parse_variable_declaration()?)
+    } else {
+      Some(self.parse_expression_statement()?)
+    };
+
+    let mut condition = None;
+    if !self.is_match(TokenEnum::Semicolon) {
+      condition = Some(self.parse_expression()?);
+    }
+    let semicolon = self
+      .consume_expected(TokenEnum::Semicolon, "Expected ';' after loop condition.")?
+      .span;
+
+    let mut increment = None;
+    if !self.is_match(TokenEnum::RightParen) {
+      increment = Some(self.parse_expression()?);
+    }
+    self.consume_expected(TokenEnum::RightParen, "Expected ')' after for clauses.")?;
+
+    let mut body = self.nested_statement(Self::parse_statement)?;
+    if let Some(increment) = increment {
+      let increment = ast::Statement::ExpressionStatement(ast::ExpressionStatement::new(increment));
+      body = ast::Statement::BlockStatement(ast::BlockStatement::new(vec![body, increment]));
+    }
+    // a missing condition loops forever.
+    let condition = condition.unwrap_or_else(|| {
+      let literal = ast::LiteralExpression::new(ast::Literal::BooleanLiteral(true), semicolon);
+      ast::Expression::Literal(literal)
+    });
+    body = ast::Statement::WhileStatement(ast::WhileStatement::new(condition, body));
+    if let Some(initializer) = initializer {
+      body = ast::Statement::BlockStatement(ast::BlockStatement::new(vec![initializer, body]));
+    }
+    return Ok(body);
+  }
+
+  // ifStmt         → "if" "(" expression ")" statement ( "else" statement )? ;
+  pub fn parse_if_statement(&mut self) -> ParseResult<ast::Statement> {
+    self.consume_expected(TokenEnum::LeftParen, "Expected '(' after 'if'.")?;
+    let condition = self.parse_expression()?;
+    self.consume_expected(TokenEnum::RightParen, "Expected ')' after if condition.")?;
+
+    let then_branch = self.nested_statement(Self::parse_statement)?;
+    let mut else_branch = None;
+    // the else binds to the nearest if.
+    if self.is_match(TokenEnum::Else) {
+      self.consume();
+      else_branch = Some(self.nested_statement(Self::parse_statement)?);
+    }
+    return Ok(ast::Statement::IfStatement(ast::IfStatement::new(
+      condition,
+      then_branch,
+      else_branch,
+    )));
   }
 
-  pub fn parse(&mut self) -> ast::Expression {
-    self.parse_expression()
+  // whileStmt      → "while" "(" expression ")" statement ;
+  pub fn parse_while_statement(&mut self) -> ParseResult<ast::Statement> {
+    self.consume_expected(TokenEnum::LeftParen, "Expected '(' after 'while'.")?;
+    let condition = self.parse_expression()?;
+    self.consume_expected(TokenEnum::RightParen, "Expected ')' after condition.")?;
+    let body = self.nested_statement(Self::parse_statement)?;
+    return Ok(ast::Statement::WhileStatement(ast::WhileStatement::new(
+      condition, body,
+    )));
   }
 
-  pub fn parse_expression(&mut self) -> ast::Expression {
-    return self.parse_equality();
+  // returnStmt     → "return" expression? ";" ;
+  pub fn parse_return_statement(&mut self) -> ParseResult<ast::Statement> {
+    let keyword = self.get_previous_token().clone();
+    let mut value = None;
+    if !self.is_match(TokenEnum::Semicolon) {
+      value = Some(self.parse_expression()?);
+    }
+    self.consume_expected(TokenEnum::Semicolon, "Expected ';' after return value.")?;
+    return Ok(ast::Statement::ReturnStatement(ast::ReturnStatement::new(
+      Box::new(keyword),
+      value,
+    )));
+  }
+
+  // printStmt      → "print" expression ";" ;
+  pub fn parse_print_statement(&mut self) -> ParseResult<ast::Statement> {
+    let expression = self.parse_expression()?;
+    self.consume_expected(TokenEnum::Semicolon, "Expected ';' after value.")?;
+    return Ok(ast::Statement::PrintStatement(ast::PrintStatement::new(expression)));
+  }
+
+  // block          → "{" declaration* "}" ;
+  pub fn parse_block(&mut self) -> ParseResult<Vec<ast::Statement>> {
+    let mut statements = vec![];
+    while !self.is_match(TokenEnum::RightBrace) && !self.is_at_end() {
+      if let Some(statement) = self.parse_declaration() {
+        statements.push(statement);
+      }
+    }
+    self.consume_expected(TokenEnum::RightBrace, "Expected '}' after block.")?;
+    return Ok(statements);
+  }
+
+  // exprStmt       → expression ";" ;
+  pub fn parse_expression_statement(&mut self) -> ParseResult<ast::Statement> {
+    let expression = self.parse_expression()?;
+    self.consume_expected(TokenEnum::Semicolon, "Expected ';' after expression.")?;
+    return Ok(ast::Statement::ExpressionStatement(ast::ExpressionStatement::new(
+      expression,
+    )));
+  }
+
+  // expression     → assignment ;
+  pub fn parse_expression(&mut self) -> ParseResult<ast::Expression> {
+    return self.nested(Self::parse_assignment);
+  }
+
+  // assignment     → ( call "." )? IDENTIFIER "=" assignment | logic_or ;
+  pub fn parse_assignment(&mut self) -> ParseResult<ast::Expression> {
+    let expression = self.parse_or()?;
+
+    if self.is_match(TokenEnum::Equal) {
+      let equals = self.consume().clone();
+      let value = self.nested(Self::parse_assignment)?;
+      if let ast::Expression::VariableExpression(variable_expression) = expression {
+        let assignment_expression = ast::AssignmentExpression::new(variable_expression.name, value);
+        return Ok(ast::Expression::AssignmentExpression(assignment_expression));
+      }
+      if let ast::Expression::GetExpression(get_expression) = expression {
+        let set_expression = ast::SetExpression::new(*get_expression.object, get_expression.name, value);
+        return Ok(ast::Expression::SetExpression(set_expression));
+      }
+      // reported, but the parser is not confused, so there is no need to synchronize.
+      let diagnostic = ErrorHandler::token_diagnostic(&equals, "Invalid assignment target.")
+        .with_label(expression.span(), "cannot assign to this expression")
+        .with_note("only variables and properties can appear on the left side of '='");
+      self.scanner.error_handler.push(diagnostic);
+    }
+    return Ok(expression);
+  }
+
+  // logic_or       → logic_and ( "or" logic_and )* ;
+  pub fn parse_or(&mut self) -> ParseResult<ast::Expression> {
+    let mut left_expression = self.parse_and()?;
+    while self.is_match(TokenEnum::Or) {
+      let token_operator = self.consume().clone();
+      let right_expression = self.parse_and()?;
+      let logical_expression = ast::LogicalExpression::new(Box::new(token_operator), left_expression, right_expression);
+      left_expression = ast::Expression::LogicalExpression(logical_expression);
+    }
+    return Ok(left_expression);
+  }
+
+  // logic_and      → equality ( "and" equality )* ;
+  pub fn parse_and(&mut self) -> ParseResult<ast::Expression> {
+    let mut left_expression = self.parse_equality()?;
+    while self.is_match(TokenEnum::And) {
+      let token_operator = self.consume().clone();
+      let right_expression = self.parse_equality()?;
+      let logical_expression = ast::LogicalExpression::new(Box::new(token_operator), left_expression, right_expression);
+      left_expression = ast::Expression::LogicalExpression(logical_expression);
+    }
+    return Ok(left_expression);
   }
 
   // equality       → comparison ( ( "!=" | "==" ) comparison )* ;
-  pub fn parse_equality(&mut self) -> ast::Expression {
-    let mut left_expression = self.parse_comparison();
+  pub fn parse_equality(&mut self) -> ParseResult<ast::Expression> {
+    let mut left_expression = self.parse_comparison()?;
 
     while self.is_match_many(&[TokenEnum::BangEqual, TokenEnum::EqualEqual]) {
       let kind_operator = self.consume().clone();
-      let right_expression = self.parse_comparison();
+      let right_expression = self.parse_comparison()?;
       let binary_expression = ast::BinaryExpression::new(Box::new(kind_operator), left_expression, right_expression);
       left_expression = ast::Expression::BinaryExpression(binary_expression);
     }
-    return left_expression;
+    return Ok(left_expression);
   }
 
   // comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
-  pub fn parse_comparison(&mut self) -> ast::Expression {
-    let mut left_expression = self.parse_term();
+  pub fn parse_comparison(&mut self) -> ParseResult<ast::Expression> {
+    let mut left_expression = self.parse_term()?;
     let matches = &[
       TokenEnum::Greater,
       TokenEnum::Less,
@@ -49,104 +378,177 @@ impl<'a> Parser<'a> {
 
     while self.is_match_many(matches) {
       let token_operator = self.consume().clone();
-      let right_expression = self.parse_term();
+      let right_expression = self.parse_term()?;
       let binary_expression = ast::BinaryExpression::new(Box::new(token_operator), left_expression, right_expression);
       left_expression = ast::Expression::BinaryExpression(binary_expression);
     }
-    return left_expression;
+    return Ok(left_expression);
   }
 
-  pub fn parse_term(&mut self) -> ast::Expression {
-    let mut left_expression = self.parse_factor();
+  pub fn parse_term(&mut self) -> ParseResult<ast::Expression> {
+    let mut left_expression = self.parse_factor()?;
     let matches = &[TokenEnum::Plus, TokenEnum::Minus];
     while self.is_match_many(matches) {
       let token_operator = self.consume().clone();
-      let right_expression = self.parse_factor();
+      let right_expression = self.parse_factor()?;
       let binary_expression = ast::BinaryExpression::new(Box::new(token_operator), left_expression, right_expression);
       left_expression = ast::Expression::BinaryExpression(binary_expression);
     }
 
-    return left_expression;
+    return Ok(left_expression);
   }
 
-  pub fn parse_factor(&mut self) -> ast::Expression {
-    let mut left_expression = self.parse_unary();
+  pub fn parse_factor(&mut self) -> ParseResult<ast::Expression> {
+    let mut left_expression = self.parse_unary()?;
     let matches = &[TokenEnum::Slash, TokenEnum::Star];
     while self.is_match_many(matches) {
       let token_operator = self.consume().clone();
-      let right_expression = self.parse_unary();
+      let right_expression = self.nested(Self::parse_unary)?;
       let binary_expression = ast::BinaryExpression::new(Box::new(token_operator), left_expression, right_expression);
       left_expression = ast::Expression::BinaryExpression(binary_expression);
     }
 
-    return left_expression;
+    return Ok(left_expression);
   }
   /*
   unary          → ( "!" | "-" ) unary
-                 | primary ;
+                 | call ;
   */
 
-  pub fn parse_unary(&mut self) -> ast::Expression {
+  pub fn parse_unary(&mut self) -> ParseResult<ast::Expression> {
     if self.is_match(TokenEnum::Bang) {
-      self.consume_expected(TokenEnum::Bang, "Expected '!' after expression.");
-      let bang_token = self.get_previous_token().clone();
-      let right_expression = self.parse_unary();
+      let bang_token = self.consume().clone();
+      let right_expression = self.nested(Self::parse_unary)?;
       let unary_expression = ast::UnaryExpression::new(Box::new(bang_token), right_expression);
-      return ast::Expression::UnaryExpression(unary_expression);
+      return Ok(ast::Expression::UnaryExpression(unary_expression));
     }
 
     if self.is_match(TokenEnum::Minus) {
-      self.consume_expected(TokenEnum::Minus, "Expected '-' after expression.");
-      let right_expression = self.parse_unary();
       let minus_token = self.consume().clone();
+      let right_expression = self.nested(Self::parse_unary)?;
       let unary_expression = ast::UnaryExpression::new(Box::new(minus_token), right_expression);
-      return ast::Expression::UnaryExpression(unary_expression);
+      return Ok(ast::Expression::UnaryExpression(unary_expression));
+    }
+    return self.parse_call();
+  }
+
+  // call           → primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+  pub fn parse_call(&mut self) -> ParseResult<ast::Expression> {
+    let mut expression = self.parse_primary()?;
+    loop {
+      if self.is_match(TokenEnum::LeftParen) {
+        self.consume();
+        expression = self.finish_call(expression)?;
+      } else if self.is_match(TokenEnum::Dot) {
+        self.consume();
+        let name = self
+          .consume_expected(TokenEnum::Identifier, "Expected property name after '.'.")?
+          .clone();
+        expression = ast::Expression::GetExpression(ast::GetExpression::new(expression, Box::new(name)));
+      } else {
+        break;
+      }
+    }
+    return Ok(expression);
+  }
+
+  // arguments      → expression ( "," expression )* ;
+  fn finish_call(&mut self, callee: ast::Expression) -> ParseResult<ast::Expression> {
+    let mut arguments = vec![];
+    if !self.is_match(TokenEnum::RightParen) {
+      loop {
+        if arguments.len() >= MAX_ARGUMENTS {
+          let token = self.peek().clone();
+          self.error(token, &format!("Can't have more than {} arguments.", MAX_ARGUMENTS));
+        }
+        arguments.push(self.parse_expression()?);
+        if !self.is_match(TokenEnum::Comma) {
+          break;
+        }
+        self.consume();
+      }
     }
-    return self.parse_primary();
+    let paren = self
+      .consume_expected(TokenEnum::RightParen, "Expected ')' after arguments.")?
+      .clone();
+    let call_expression = ast::CallExpression::new(callee, Box::new(paren), arguments);
+    return Ok(ast::Expression::CallExpression(call_expression));
   }
 
   /*
-  primary        → NUMBER | STRING | "true" | "false" | "nil"
-                 | "(" expression ")" ;
+  primary        → NUMBER | STRING | "true" | "false" | "nil" | "this"
+                 | "super" "." IDENTIFIER
+                 | "(" expression ")" | IDENTIFIER ;
   */
-  pub fn parse_primary(&mut self) -> ast::Expression {
+  pub fn parse_primary(&mut self) -> ParseResult<ast::Expression> {
     // boolean's
     if self.is_match(TokenEnum::False) {
-      self.consume();
-      let literal = ast::Literal::BooleanLiteral(true);
-      return ast::Expression::Literal(literal);
+      let span = self.consume().span;
+      let literal = ast::Literal::BooleanLiteral(false);
+      return Ok(ast::Expression::Literal(ast::LiteralExpression::new(literal, span)));
     }
     if self.is_match(TokenEnum::True) {
-      self.consume();
-      let literal = ast::Literal::BooleanLiteral(false);
-      return ast::Expression::Literal(literal);
+      let span = self.consume().span;
+      let literal = ast::Literal::BooleanLiteral(true);
+      return Ok(ast::Expression::Literal(ast::LiteralExpression::new(literal, span)));
     }
 
     if self.is_match(TokenEnum::Nil) {
-      self.consume();
+      let span = self.consume().span;
       let literal = ast::Literal::NullLiteral;
-      return ast::Expression::Literal(literal);
+      return Ok(ast::Expression::Literal(ast::LiteralExpression::new(literal, span)));
     }
 
-    if self.is_match_many(&vec![TokenEnum::StringLiteral, TokenEnum::NumberLiteral]) {
+    if self.is_match_many(&[TokenEnum::StringLiteral, TokenEnum::NumberLiteral]) {
       let previous_token = self.consume();
+      let span = previous_token.span;
       if previous_token.kind == TokenEnum::StringLiteral {
         let literal = ast::Literal::StringLiteral(previous_token.literal.clone());
-        return ast::Expression::Literal(literal);
+        return Ok(ast::Expression::Literal(ast::LiteralExpression::new(literal, span)));
       }
       let literal = ast::Literal::NumberLiteral(previous_token.literal.parse::<f64>().unwrap());
-      return ast::Expression::Literal(literal);
+      return Ok(ast::Expression::Literal(ast::LiteralExpression::new(literal, span)));
+    }
+
+    if self.is_match(TokenEnum::Super) {
+      let keyword = self.consume().clone();
+      self.consume_expected(TokenEnum::Dot, "Expected '.' after 'super'.")?;
+      let method = self
+        .consume_expected(TokenEnum::Identifier, "Expected superclass method name.")?
+        .clone();
+      let super_expression = ast::SuperExpression::new(Box::new(keyword), Box::new(method));
+      return Ok(ast::Expression::SuperExpression(super_expression));
+    }
+
+    if self.is_match(TokenEnum::This) {
+      let keyword = self.consume().clone();
+      return Ok(ast::Expression::ThisExpression(ast::ThisExpression::new(Box::new(
+        keyword,
+      ))));
     }
 
     if self.is_match(TokenEnum::LeftParen) {
-      let expression = self.parse_expression();
-      self.consume_expected(TokenEnum::RightParen, "Expected ')' after expression.");
-      let expression = ast::GroupingExpression::new(expression);
-      return ast::Expression::GroupingExpression(expression);
+      let left_paren = self.consume().span;
+      let expression = self.parse_expression()?;
+      if !self.is_match(TokenEnum::RightParen) {
+        let token = self.peek().clone();
+        let diagnostic = ErrorHandler::token_diagnostic(&token, "Expected ')' after expression.")
+          .with_label(left_paren, "unclosed delimiter");
+        self.scanner.error_handler.push(diagnostic);
+        return Err(ParseError);
+      }
+      let right_paren = self.consume().span;
+      let expression = ast::GroupingExpression::new(expression, left_paren.to(right_paren));
+      return Ok(ast::Expression::GroupingExpression(expression));
+    }
+
+    if self.is_match(TokenEnum::Identifier) {
+      let name = self.consume().clone();
+      let variable_expression = ast::VariableExpression::new(Box::new(name));
+      return Ok(ast::Expression::VariableExpression(variable_expression));
     }
     let token = self.peek().clone();
-    self.error(token, "Expected expression.");
-    process::exit(ERROR_EXIT_CODE);
+    return Err(self.error(token, "Expected expression."));
   }
 
   // helper methods
@@ -166,6 +568,7 @@ impl<'a> Parser<'a> {
     &self.scanner.tokens[self.current_token_pointer]
   }
 
+  #[allow(dead_code)]
   fn peek_next(&self) -> &Token {
     &self.scanner.tokens[self.current_token_pointer + 1]
   }
@@ -191,6 +594,7 @@ impl<'a> Parser<'a> {
     return expecteds.iter().any(|expected| self.peek().kind.clone() == *expected);
   }
 
+  #[allow(dead_code)]
   fn is_match_next(&self, expected: TokenEnum) -> bool {
     if self.is_at_end() {
       return false;
@@ -198,25 +602,72 @@ impl<'a> Parser<'a> {
     return self.peek_next().kind.clone() == expected;
   }
 
-  fn consume_expected(&mut self, expected: TokenEnum, message: &str) {
+  fn consume_expected(&mut self, expected: TokenEnum, message: &str) -> ParseResult<&Token> {
     if !self.is_match(expected) {
       let token = self.peek().clone();
-      self.error(token, message);
-      process::exit(ERROR_EXIT_CODE);
+      return Err(self.error(token, message));
+    }
+    return Ok(self.consume());
+  }
+
+  // discards tokens until a likely statement boundary, so one mistake doesn't cascade into many.
+  fn synchronize(&mut self) {
+    if !self.is_at_end() {
+      self.consume();
+    }
+    while !self.is_at_end() {
+      if self.get_previous_token().kind == TokenEnum::Semicolon {
+        return;
+      }
+      match self.peek().kind {
+        TokenEnum::Class
+        | TokenEnum::Fun
+        | TokenEnum::Var
+        | TokenEnum::For
+        | TokenEnum::If
+        | TokenEnum::While
+        | TokenEnum::Print
+        | TokenEnum::Return => return,
+        _ => {
+          self.consume();
+        }
+      }
+    }
+  }
+
+  // runs `parse` one level deeper, or reports that expressions are nested too deeply.
+  fn nested(&mut self, parse: fn(&mut Self) -> ParseResult<ast::Expression>) -> ParseResult<ast::Expression> {
+    if self.expression_depth == MAX_EXPRESSION_DEPTH {
+      let token = self.peek().clone();
+      return Err(self.error(token, "Expression nested too deeply."));
+    }
+    self.expression_depth += 1;
+    let expression = parse(self);
+    self.expression_depth -= 1;
+    return expression;
+  }
+
+  // like `nested`, for statements. past the limit there is no sensible place to resume, so the
+  // rest of the input is skipped rather than reporting every block it leaves unclosed.
+  fn nested_statement<T>(&mut self, parse: fn(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+    if self.statement_depth == MAX_STATEMENT_DEPTH {
+      let token = self.peek().clone();
+      let error = self.error(token, "Statement nested too deeply.");
+      self.gave_up = true;
+      self.current_token_pointer = self.scanner.tokens.len() - 1;
+      return Err(error);
     }
-    self.consume();
+    self.statement_depth += 1;
+    let statement = parse(self);
+    self.statement_depth -= 1;
+    return statement;
   }
 
   // error handling
-  fn error(&mut self, token: Token, message: &str) {
-    if token.kind == TokenEnum::EndOfFile {
-      self.scanner.error_handler.report(token.line, "at end", message);
-      // todo: exit with error code
-      // process::exit(ERROR_EXIT_CODE);
-    }
-    let lexeme_formated = &format!("at '{}'", token.lexeme);
-    self.scanner.error_handler.report(token.line, lexeme_formated, message);
-    // todo: exit with error code
-    // process::exit(ERROR_EXIT_CODE);
+  fn error(&mut self, token: Token, message: &str) -> ParseError {
+    if !self.gave_up {
+      self.scanner.error_handler.report_rich(&token, message);
+    }
+    ParseError
   }
 }
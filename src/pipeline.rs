@@ -0,0 +1,113 @@
+use std::rc::Rc;
+
+use crate::diagnostics::Diagnostic;
+use crate::evaluator::Evaluator;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::shared::constants::{COMPILE_ERROR_EXIT_CODE, RUNTIME_ERROR_EXIT_CODE, SUCCESS_EXIT_CODE};
+use crate::shared::options::{Backend, RunOptions};
+use crate::shared::output::Output;
+use crate::vm::{verify, Assembler, Compiler, FunctionProto, Vm, VmStats};
+
+/*
+the way from source text to a finished run: scan → parse → resolve, then either evaluate
+the tree or compile and run it on the vm. `lox run` and `lox test-suite` both go through
+here, so the conformance suite checks the same code path users run.
+
+nothing is printed: errors come back as diagnostics and `print` goes to the given `Output`.
+*/
+
+// what a run produced.
+pub struct Outcome {
+  pub compile_errors: Vec<Diagnostic>, // scan, parse, resolve, compile, assemble or verify
+  pub runtime_error: Option<Diagnostic>,
+  pub output: Vec<String>,    // what `print` wrote, when it was captured
+  pub stats: Option<VmStats>, // set when the program ran on the vm
+}
+
+impl Outcome {
+  fn compile_errors(errors: Vec<Diagnostic>) -> Self {
+    Outcome { compile_errors: errors, runtime_error: None, output: vec![], stats: None }
+  }
+
+  pub fn exit_code(&self) -> i32 {
+    match (self.compile_errors.is_empty(), &self.runtime_error) {
+      (false, _) => COMPILE_ERROR_EXIT_CODE,
+      (true, Some(_)) => RUNTIME_ERROR_EXIT_CODE,
+      (true, None) => SUCCESS_EXIT_CODE,
+    }
+  }
+}
+
+// a `.hex` program, on whichever backend the options ask for.
+pub fn run_source(source: &str, path_name: &str, options: &RunOptions, output: Output) -> Outcome {
+  if options.backend == Backend::Vm {
+    return match compile_source(source, path_name) {
+      Ok(script) => run_script(script, options, output),
+      Err(errors) => Outcome::compile_errors(errors),
+    };
+  }
+  let mut scanner = Scanner::new(source.to_string(), path_name);
+  let mut parser = Parser::new(&mut scanner);
+  let (program, mut errors) = parser.parse();
+  let mut resolver = Resolver::new();
+  if errors.is_empty() {
+    errors = resolver.resolve(&program);
+  }
+  if !errors.is_empty() {
+    return Outcome::compile_errors(errors);
+  }
+  let mut evaluator = Evaluator::new(path_name);
+  evaluator.add_locals(resolver.into_locals());
+  evaluator.truthiness = options.truthiness;
+  evaluator.output = output;
+  let result = evaluator.interpret(&program);
+  Outcome {
+    compile_errors: vec![],
+    runtime_error: result.err().map(|error| error.to_diagnostic()),
+    output: evaluator.output.take_lines(),
+    stats: None,
+  }
+}
+
+// a `.hexasm` program, which always runs on the vm.
+pub fn run_assembly(source: &str, options: &RunOptions, output: Output) -> Outcome {
+  match assemble_source(source) {
+    Ok(script) => run_script(script, options, output),
+    Err(errors) => Outcome::compile_errors(errors),
+  }
+}
+
+// compiled code, already verified if it didn't come from the compiler.
+pub fn run_script(script: Rc<FunctionProto>, options: &RunOptions, output: Output) -> Outcome {
+  let mut vm = Vm::with_options(options);
+  vm.output = output;
+  let result = vm.interpret(script);
+  Outcome {
+    compile_errors: vec![],
+    runtime_error: result.err().map(|error| error.to_diagnostic()),
+    output: vm.output.take_lines(),
+    stats: Some(vm.stats()),
+  }
+}
+
+pub fn compile_source(source: &str, path_name: &str) -> Result<Rc<FunctionProto>, Vec<Diagnostic>> {
+  let mut scanner = Scanner::new(source.to_string(), path_name);
+  let mut parser = Parser::new(&mut scanner);
+  let (program, mut errors) = parser.parse();
+  if errors.is_empty() {
+    errors = Resolver::new().resolve(&program);
+  }
+  match errors.is_empty() {
+    true => Compiler::new(source).compile(&program),
+    false => Err(errors),
+  }
+}
+
+// hand-written bytecode is verified as well, since nothing else checked it.
+pub fn assemble_source(source: &str) -> Result<Rc<FunctionProto>, Vec<Diagnostic>> {
+  let script = Assembler::new(source).assemble()?;
+  verify(&script)?;
+  Ok(script)
+}
@@ -1,33 +1,70 @@
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
 
+use crate::ast;
 use crate::evaluator::Evaluator;
-// use crate::execute;
 use crate::parser::Parser;
+use crate::resolver::Resolver;
 use crate::scanner::Scanner;
+use crate::shared::options::RunOptions;
 use crate::values::Value;
-use crate::visitor::print_visitor::PrintVisitor;
-// use crate::visitor::print_visitor::PrintVisitor;
 
-fn run_code(raw: &str, scanner: &mut Scanner) -> Value {
+// the evaluator outlives a single line so variables stay defined across the session.
+fn run_code(scanner: &mut Scanner, evaluator: &mut Evaluator) -> Option<Value> {
+  evaluator
+    .error_handler
+    .set_source(scanner.error_handler.source.as_str());
   let mut parser = Parser::new(scanner);
-  let ast = parser.parse();
-  let mut visitor = PrintVisitor;
-  let debug = ast.accept(&mut visitor);
-  // println!("debug: {}", debug);
-  let mut evaluator = Evaluator::new("REPL");
-  let result = evaluator.evaluate(&ast);
-  return result;
+  let (program, errors) = parser.parse();
+  if !errors.is_empty() {
+    for error in &errors {
+      scanner.error_handler.print(error);
+    }
+    return None;
+  }
+  let mut resolver = Resolver::new();
+  let errors = resolver.resolve(&program);
+  if !errors.is_empty() {
+    for error in &errors {
+      scanner.error_handler.print(error);
+    }
+    return None;
+  }
+  evaluator.add_locals(resolver.into_locals());
+  let result = match evaluator.interpret(&program) {
+    Ok(result) => result,
+    Err(error) => {
+      evaluator.report_runtime_error(&error);
+      evaluator.error_handler.flush();
+      evaluator.error_handler.reset();
+      return None;
+    }
+  };
+  match program.statements.last() {
+    Some(ast::Statement::ExpressionStatement(_)) => Some(result),
+    _ => None,
+  }
 }
 
-pub fn prompt() {
+// lets expressions be typed without the trailing semicolon.
+fn complete_line(line: &str) -> String {
+  let trimmed = line.trim_end();
+  if trimmed.ends_with(';') || trimmed.ends_with('}') {
+    return trimmed.to_string();
+  }
+  format!("{};", trimmed)
+}
+
+pub fn prompt(options: &RunOptions) {
   let mut rl = DefaultEditor::new().unwrap();
   let warning_exit = "(To exit, press Ctrl+C again or Ctrl+D or type .exit)";
   let welcome = "Welcome to the Lox REPL!";
   let exit_commands = [".exit"];
   let prompt = ">> ";
   let mut ctrl_d = false;
-  if rl.load_history("history.txt").is_err() {}
+  let mut evaluator = Evaluator::new("repl");
+  evaluator.truthiness = options.truthiness;
+  let _ = rl.load_history("history.txt");
 
   println!("{}", welcome);
   loop {
@@ -37,16 +74,12 @@ pub fn prompt() {
         if exit_commands.contains(&line.trim()) {
           break;
         }
-        let mut scanner = Scanner::new(line.to_string(), "repl");
-        if scanner.error_handler.had_error {
-          scanner.error_handler.had_error = false;
+        if line.trim().is_empty() {
           continue;
         }
-        let result = run_code(&line, &mut scanner);
-        println!("Result: {}", result);
-        if scanner.error_handler.had_error {
-          scanner.error_handler.had_error = false;
-          continue;
+        let mut scanner = Scanner::new(complete_line(&line), "repl");
+        if let Some(result) = run_code(&mut scanner, &mut evaluator) {
+          println!("Result: {}", result);
         }
         rl.add_history_entry(line.as_str()).unwrap();
       }
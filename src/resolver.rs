@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast;
+use crate::ast::tokens::Token;
+use crate::ast::Span;
+use crate::diagnostics::error_handler::ErrorHandler;
+use crate::diagnostics::Diagnostic;
+use crate::shared::symbol::Symbol;
+use crate::visitor::Visitor;
+
+/*
+a static pass between parsing and evaluation. it records, for every local variable
+use, how many scopes separate it from its declaration, and reports the errors that
+can be found without running the program. globals are left unresolved and looked up
+dynamically, so they can be used before they are declared.
+*/
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+  None,
+  Function,
+  Initializer,
+  Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassKind {
+  None,
+  Class,
+  Subclass,
+}
+
+// a declared name; `defined` stays false while its initializer is being resolved.
+struct Binding {
+  defined: bool,
+  span: Span,
+}
+
+pub struct Resolver {
+  // scope distance of every local variable use, for the evaluator.
+  locals: HashMap<ast::NodeId, usize>,
+  scopes: Vec<HashMap<Symbol, Binding>>,
+  current_function: FunctionKind,
+  current_class: ClassKind,
+  diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+  pub fn new() -> Self {
+    Resolver {
+      locals: HashMap::new(),
+      scopes: vec![],
+      current_function: FunctionKind::None,
+      current_class: ClassKind::None,
+      diagnostics: vec![],
+    }
+  }
+
+  // returns every static error found, in source order.
+  pub fn resolve(&mut self, program: &ast::Program) -> Vec<Diagnostic> {
+    program.accept(self);
+    let mut errors = std::mem::take(&mut self.diagnostics);
+    errors.sort_by_key(|error| error.line);
+    errors
+  }
+
+  pub fn into_locals(self) -> HashMap<ast::NodeId, usize> {
+    self.locals
+  }
+
+  fn resolve_statements(&mut self, statements: &[ast::Statement]) {
+    for statement in statements {
+      statement.accept(self);
+    }
+  }
+
+  fn resolve_function(&mut self, function: &ast::FunctionStatement, kind: FunctionKind) {
+    let enclosing_function = std::mem::replace(&mut self.current_function, kind);
+    self.begin_scope();
+    for param in &function.params {
+      self.declare(param);
+      self.define(param);
+    }
+    self.resolve_statements(&function.body);
+    self.end_scope();
+    self.current_function = enclosing_function;
+  }
+
+  // the innermost scope that declares `name`; globals are not recorded.
+  fn resolve_local(&mut self, id: ast::NodeId, name: &Symbol) {
+    for (depth, scope) in self.scopes.iter().rev().enumerate() {
+      if scope.contains_key(name) {
+        self.locals.insert(id, depth);
+        return;
+      }
+    }
+  }
+
+  fn begin_scope(&mut self) {
+    self.scopes.push(HashMap::new());
+  }
+
+  fn end_scope(&mut self) {
+    self.scopes.pop();
+  }
+
+  fn declare(&mut self, name: &Token) {
+    let Some(scope) = self.scopes.last_mut() else {
+      return;
+    };
+    if let Some(previous) = scope.get(&name.lexeme) {
+      let diagnostic = ErrorHandler::token_diagnostic(name, "Already a variable with this name in this scope.")
+        .with_label(previous.span, "first declared here");
+      self.diagnostics.push(diagnostic);
+      return;
+    }
+    scope.insert(name.lexeme.clone(), Binding { defined: false, span: name.span });
+  }
+
+  fn define(&mut self, name: &Token) {
+    if let Some(binding) = self.scopes.last_mut().and_then(|scope| scope.get_mut(&name.lexeme)) {
+      binding.defined = true;
+    }
+  }
+
+  // binds a name that does not come from a declaration in the source, like `this`.
+  fn define_implicit(&mut self, name: &str, span: Span) {
+    if let Some(scope) = self.scopes.last_mut() {
+      scope.insert(Symbol::intern(name), Binding { defined: true, span });
+    }
+  }
+
+  fn error(&mut self, token: &Token, message: &str) {
+    self.diagnostics.push(ErrorHandler::token_diagnostic(token, message));
+  }
+}
+
+impl Visitor<()> for Resolver {
+  fn visit(&mut self, expression: &ast::Expression) {
+    expression.accept(self)
+  }
+
+  fn visit_program(&mut self, program: &ast::Program) {
+    self.resolve_statements(&program.statements);
+  }
+
+  fn visit_statement(&mut self, statement: &ast::Statement) {
+    statement.accept(self)
+  }
+
+  fn visit_print_statement(&mut self, print_statement: &ast::PrintStatement) {
+    print_statement.expression.accept(self);
+  }
+
+  fn visit_expression_statement(&mut self, expression_statement: &ast::ExpressionStatement) {
+    expression_statement.expression.accept(self);
+  }
+
+  fn visit_variable_statement(&mut self, variable_statement: &ast::VariableStatement) {
+    self.declare(&variable_statement.name);
+    if let Some(initializer) = &variable_statement.initializer {
+      initializer.accept(self);
+    }
+    self.define(&variable_statement.name);
+  }
+
+  fn visit_block_statement(&mut self, block_statement: &ast::BlockStatement) {
+    self.begin_scope();
+    self.resolve_statements(&block_statement.statements);
+    self.end_scope();
+  }
+
+  fn visit_if_statement(&mut self, if_statement: &ast::IfStatement) {
+    if_statement.condition.accept(self);
+    if_statement.then_branch.accept(self);
+    if let Some(else_branch) = &if_statement.else_branch {
+      else_branch.accept(self);
+    }
+  }
+
+  fn visit_while_statement(&mut self, while_statement: &ast::WhileStatement) {
+    while_statement.condition.accept(self);
+    while_statement.body.accept(self);
+  }
+
+  // the name is defined before the body so the function can refer to itself.
+  fn visit_function_statement(&mut self, function_statement: &Rc<ast::FunctionStatement>) {
+    self.declare(&function_statement.name);
+    self.define(&function_statement.name);
+    self.resolve_function(function_statement, FunctionKind::Function);
+  }
+
+  fn visit_return_statement(&mut self, return_statement: &ast::ReturnStatement) {
+    if self.current_function == FunctionKind::None {
+      self.error(&return_statement.keyword, "Can't return from top-level code.");
+    }
+    if let Some(value) = &return_statement.value {
+      if self.current_function == FunctionKind::Initializer {
+        self.error(&return_statement.keyword, "Can't return a value from an initializer.");
+      }
+      value.accept(self);
+    }
+  }
+
+  // mirrors the scopes the evaluator creates: one holding `super` for subclasses,
+  // then one holding `this` for every bound method.
+  fn visit_class_statement(&mut self, class_statement: &ast::ClassStatement) {
+    let enclosing_class = std::mem::replace(&mut self.current_class, ClassKind::Class);
+    self.declare(&class_statement.name);
+    self.define(&class_statement.name);
+
+    if let Some(superclass) = &class_statement.superclass {
+      if superclass.name.lexeme == class_statement.name.lexeme {
+        self.error(&superclass.name, "A class can't inherit from itself.");
+      }
+      self.current_class = ClassKind::Subclass;
+      self.visit_variable_expression(superclass);
+      self.begin_scope();
+      self.define_implicit("super", superclass.span);
+    }
+
+    self.begin_scope();
+    self.define_implicit("this", class_statement.name.span);
+    for method in &class_statement.methods {
+      let kind = match method.name.lexeme.as_str() {
+        "init" => FunctionKind::Initializer,
+        _ => FunctionKind::Method,
+      };
+      self.resolve_function(method, kind);
+    }
+    self.end_scope();
+
+    if class_statement.superclass.is_some() {
+      self.end_scope();
+    }
+    self.current_class = enclosing_class;
+  }
+
+  fn visit_binary_expression(&mut self, binary_expression: &ast::BinaryExpression) {
+    binary_expression.left.accept(self);
+    binary_expression.right.accept(self);
+  }
+
+  fn visit_unary_expression(&mut self, unary_expression: &ast::UnaryExpression) {
+    unary_expression.right.accept(self);
+  }
+
+  fn visit_logical_expression(&mut self, logical_expression: &ast::LogicalExpression) {
+    logical_expression.left.accept(self);
+    logical_expression.right.accept(self);
+  }
+
+  fn visit_call_expression(&mut self, call_expression: &ast::CallExpression) {
+    call_expression.callee.accept(self);
+    for argument in &call_expression.arguments {
+      argument.accept(self);
+    }
+  }
+
+  fn visit_get_expression(&mut self, get_expression: &ast::GetExpression) {
+    get_expression.object.accept(self);
+  }
+
+  fn visit_set_expression(&mut self, set_expression: &ast::SetExpression) {
+    set_expression.value.accept(self);
+    set_expression.object.accept(self);
+  }
+
+  fn visit_this_expression(&mut self, this_expression: &ast::ThisExpression) {
+    if self.current_class == ClassKind::None {
+      self.error(&this_expression.keyword, "Can't use 'this' outside of a class.");
+      return;
+    }
+    self.resolve_local(this_expression.id, &this_expression.keyword.lexeme);
+  }
+
+  fn visit_super_expression(&mut self, super_expression: &ast::SuperExpression) {
+    match self.current_class {
+      ClassKind::None => self.error(&super_expression.keyword, "Can't use 'super' outside of a class."),
+      ClassKind::Class => self.error(
+        &super_expression.keyword,
+        "Can't use 'super' in a class with no superclass.",
+      ),
+      ClassKind::Subclass => {}
+    }
+    self.resolve_local(super_expression.id, &super_expression.keyword.lexeme);
+  }
+
+  fn visit_grouping_expression(&mut self, grouping_expression: &ast::GroupingExpression) {
+    grouping_expression.expression.accept(self);
+  }
+
+  fn visit_variable_expression(&mut self, variable_expression: &ast::VariableExpression) {
+    let name = &variable_expression.name;
+    let declared_but_undefined = self
+      .scopes
+      .last()
+      .and_then(|scope| scope.get(&name.lexeme))
+      .is_some_and(|binding| !binding.defined);
+    if declared_but_undefined {
+      self.error(name, "Can't read local variable in its own initializer.");
+    }
+    self.resolve_local(variable_expression.id, &name.lexeme);
+  }
+
+  fn visit_assignment_expression(&mut self, assignment_expression: &ast::AssignmentExpression) {
+    assignment_expression.value.accept(self);
+    self.resolve_local(assignment_expression.id, &assignment_expression.name.lexeme);
+  }
+
+  fn visit_literal(&mut self, _literal: &ast::Literal) {}
+
+  fn visit_expression(&mut self, expression: &ast::Expression) {
+    expression.accept(self)
+  }
+}
@@ -1,16 +1,21 @@
 // use std::collections::HashMap;
 
 use crate::ast::tokens::{Token, TokenEnum};
+use crate::ast::Span;
 use crate::diagnostics::error_handler::ErrorHandler;
+use crate::shared::symbol::Symbol;
 
 pub struct Scanner {
   pub tokens: Vec<Token>,
   pub error_handler: ErrorHandler,
-  raw: String,   // the source code
-  cursor: usize, // index of the current character
-  line: usize,   // current line number
-  start: usize,  // index of the start of the current token
-                 // keywords: HashMap<&'static str, TokenEnum>,
+  raw: String,       // the source code
+  cursor: usize,     // byte offset of the current character
+  line: usize,       // current line number
+  line_start: usize, // byte offset where the current line begins
+  start: usize,      // byte offset of the start of the current token
+  start_line: usize, // line where the current token starts
+  start_column: usize, // column where the current token starts
+                     // keywords: HashMap<&'static str, TokenEnum>,
 }
 
 fn check_keyword(text: &str) -> Option<TokenEnum> {
@@ -29,6 +34,8 @@ fn check_keyword(text: &str) -> Option<TokenEnum> {
     "super" => TokenEnum::Super,
     "this" => TokenEnum::This,
     "true" => TokenEnum::True,
+    "var" => TokenEnum::Var,
+    "while" => TokenEnum::While,
     // equivalent to None (bacause is not possible to return diferent types)
     _ => TokenEnum::Identifier,
   };
@@ -36,9 +43,10 @@ fn check_keyword(text: &str) -> Option<TokenEnum> {
   if kind == TokenEnum::Identifier {
     return None;
   }
-  return Some(kind);
+  Some(kind)
 }
 
+#[allow(clippy::needless_return)]
 impl Scanner {
   pub fn new(raw: String, path_name: &str) -> Self {
     // let keywords = HashMap::from([
@@ -56,19 +64,43 @@ impl Scanner {
     //   ("super", TokenEnum::Super),
     //   ("this", TokenEnum::This),
     //   ("true", TokenEnum::True),
+    //   ("var", TokenEnum::Var),
+    //   ("while", TokenEnum::While),
     // ]);
     //
-    let error_handler = ErrorHandler::new(path_name);
-    Scanner { tokens: vec![], raw, cursor: 0, line: 1, start: 0, error_handler }
+    let error_handler = ErrorHandler::with_source(path_name, &raw);
+    Scanner {
+      tokens: vec![],
+      raw,
+      cursor: 0,
+      line: 1,
+      line_start: 0,
+      start: 0,
+      start_line: 1,
+      start_column: 1,
+      error_handler,
+    }
   }
   pub fn scan_tokens(&mut self) {
     while !self.is_at_end() {
-      self.start = self.cursor;
+      self.begin_token();
       self.scan_token();
     }
+    self.begin_token();
     self.add_token(TokenEnum::EndOfFile);
   }
 
+  fn begin_token(&mut self) {
+    self.start = self.cursor;
+    self.start_line = self.line;
+    self.start_column = self.raw[self.line_start..self.cursor].chars().count() + 1;
+  }
+
+  fn new_line(&mut self) {
+    self.line += 1;
+    self.line_start = self.cursor;
+  }
+
   pub fn scan_token(&mut self) {
     let character = self.advance();
     match character {
@@ -81,10 +113,15 @@ impl Scanner {
       '-' => self.add_token(TokenEnum::Minus),
       '+' => self.add_token(TokenEnum::Plus),
       ';' => self.add_token(TokenEnum::Semicolon),
-      '/' => self.add_token(TokenEnum::Slash),
       '*' => self.add_token(TokenEnum::Star),
-      '!' => self.add_token(TokenEnum::Bang),
       // One or two character tokens.'
+      '!' => {
+        if self.is_match('=') {
+          self.add_token(TokenEnum::BangEqual);
+        } else {
+          self.add_token(TokenEnum::Bang);
+        }
+      }
       '=' => {
         if self.is_match('=') {
           self.add_token(TokenEnum::EqualEqual);
@@ -107,12 +144,6 @@ impl Scanner {
         }
       }
 
-      // or reserved words
-      'o' => {
-        if self.is_match('r') {
-          self.add_token(TokenEnum::Or);
-        }
-      }
       // string literals
       '"' => self.scan_string(),
       // reversed order
@@ -130,7 +161,7 @@ impl Scanner {
           return;
         }
         if current_character == '\n' {
-          self.line += 1;
+          self.new_line();
           return;
         }
 
@@ -149,25 +180,31 @@ impl Scanner {
           return;
         }
         let message = format!("Unexpected character: {}", current_character);
-        self.error_handler.error(self.line, &message);
+        self
+          .error_handler
+          .error_at(Span::new(self.start, self.cursor), self.line, &message);
       }
     }
   }
 
   pub fn scan_string(&mut self) {
     while !self.is_at_end() && self.peek_char() != '"' {
-      if self.peek_char() == '\n' {
-        self.line += 1;
+      let character = self.advance();
+      if character == '\n' {
+        self.new_line();
       }
-      self.advance();
     }
     if self.is_at_end() {
-      self.error_handler.error(self.line, "Unterminated string.");
+      let span = Span::new(self.start, self.cursor);
+      self
+        .error_handler
+        .error_at(span, self.start_line, "Unterminated string.");
+      return;
     }
     // the closing ".
     self.advance();
 
-    let literal = self.raw[self.start + 1..self.cursor - 1].to_string();
+    let literal = Symbol::intern(&self.raw[self.start + 1..self.cursor - 1]);
     self.add_token_with_literal(TokenEnum::StringLiteral, literal);
   }
 
@@ -193,7 +230,7 @@ impl Scanner {
     //   }
     // }
     //
-    let literal = self.raw[self.start..self.cursor].to_string();
+    let literal = Symbol::intern(&self.raw[self.start..self.cursor]);
     self.add_token_with_literal(TokenEnum::NumberLiteral, literal);
   }
 
@@ -209,33 +246,35 @@ impl Scanner {
   }
   pub fn advance(&mut self) -> char {
     let character = self.peek_char();
-    self.cursor += 1;
+    self.cursor += character.len_utf8();
     character
   }
 
   pub fn peek_char(&mut self) -> char {
-    if self.is_at_end() {
-      return '\0';
-    }
-    self.raw.chars().nth(self.cursor).unwrap()
+    self.raw[self.cursor..].chars().next().unwrap_or('\0')
   }
 
   pub fn peek_next_char(&mut self) -> char {
-    if self.is_at_end() {
-      return '\0';
-    }
-    self.raw.chars().nth(self.cursor + 1).unwrap()
+    let mut characters = self.raw[self.cursor..].chars();
+    characters.next();
+    characters.next().unwrap_or('\0')
   }
 
   pub fn add_token(&mut self, kind: TokenEnum) {
-    let lexeme = self.raw[self.start..self.cursor].to_string();
-    let literal = String::from("");
-    self.tokens.push(Token::new(kind, lexeme, literal, self.line));
+    self.add_token_with_literal(kind, Symbol::intern(""));
   }
 
-  pub fn add_token_with_literal(&mut self, kind: TokenEnum, literal: String) {
-    let lexeme = self.raw[self.start..self.cursor].to_string();
-    self.tokens.push(Token::new(kind, lexeme, literal, self.line));
+  pub fn add_token_with_literal(&mut self, kind: TokenEnum, literal: Symbol) {
+    let lexeme = Symbol::intern(&self.raw[self.start..self.cursor]);
+    let span = Span::new(self.start, self.cursor);
+    self.tokens.push(Token::new(
+      kind,
+      lexeme,
+      literal,
+      span,
+      self.start_line,
+      self.start_column,
+    ));
   }
 
   pub fn get_reserved_keyword_or_return_identifier(&mut self, text: &str) -> TokenEnum {
@@ -252,14 +291,10 @@ impl Scanner {
   }
 
   pub fn is_match(&mut self, expected: char) -> bool {
-    if self.is_at_end() {
+    if self.is_at_end() || self.peek_char() != expected {
       return false;
     }
-    let actual = self.raw.chars().nth(self.cursor).unwrap();
-    if actual != expected {
-      return false;
-    }
-    self.cursor += 1;
+    self.advance();
     true
   }
 
@@ -268,13 +303,14 @@ impl Scanner {
   }
 
   pub fn is_alphabetic(&mut self, character: char) -> bool {
-    return character >= 'a' && character <= 'z' || character >= 'A' && character <= 'Z' || character == '_';
+    return character.is_ascii_alphabetic() || character == '_';
   }
 
   pub fn is_alphanumeric(&mut self, character: char) -> bool {
     return self.is_alphabetic(character) || self.is_digit(character);
   }
 
+  #[allow(dead_code)]
   pub fn is_keyword(&mut self, text: &str) -> bool {
     return check_keyword(text).is_some();
   }
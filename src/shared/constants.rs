@@ -1,2 +1,12 @@
 pub const ERROR_EXIT_CODE: i32 = 1;
 pub const SUCCESS_EXIT_CODE: i32 = 0;
+// follows the sysexits.h convention used by the reference lox implementations.
+pub const COMPILE_ERROR_EXIT_CODE: i32 = 65; // EX_DATAERR
+pub const RUNTIME_ERROR_EXIT_CODE: i32 = 70; // EX_SOFTWARE
+pub const IO_ERROR_EXIT_CODE: i32 = 74; // EX_IOERR
+
+// deepest lox call nesting either backend allows before reporting "Stack overflow.".
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+// the tree-walking evaluator recurses on the native stack for every lox call.
+pub const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
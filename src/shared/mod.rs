@@ -1,2 +1,5 @@
 pub mod constants;
+pub mod options;
+pub mod output;
+pub mod symbol;
 pub mod validation;
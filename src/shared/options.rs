@@ -0,0 +1,40 @@
+use crate::values::Truthiness;
+
+// which implementation executes the program.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Backend {
+  // walks the AST directly.
+  TreeWalker,
+  // compiles to bytecode and runs it on the stack vm.
+  #[default]
+  Vm,
+}
+
+// settings from the command line that change how a program runs.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+  pub truthiness: Truthiness,
+  pub backend: Backend,
+  pub trace: bool, // only the vm can trace execution
+  pub gc_stress: bool,
+  pub gc_growth_factor: Option<f64>, // the heap's default when not given
+  pub stats: bool,                   // only the vm keeps inline cache and gc counters
+}
+
+impl RunOptions {
+  pub fn from_matches(matches: &clap::ArgMatches) -> Self {
+    let truthiness = match matches.get_flag("loose-truthiness") {
+      true => Truthiness::Loose,
+      false => Truthiness::Lox,
+    };
+    let backend = match matches.get_one::<String>("backend").map(String::as_str) {
+      Some("tree-walker") => Backend::TreeWalker,
+      _ => Backend::Vm,
+    };
+    let trace = matches.get_flag("trace");
+    let gc_stress = matches.get_flag("gc-stress");
+    let gc_growth_factor = matches.get_one::<f64>("gc-growth-factor").copied();
+    let stats = matches.get_flag("stats");
+    RunOptions { truthiness, backend, trace, gc_stress, gc_growth_factor, stats }
+  }
+}
@@ -0,0 +1,25 @@
+// where `print` writes to: the process stdout, or a buffer when the output is checked afterwards.
+pub enum Output {
+  Stdout,
+  Captured(Vec<String>),
+}
+
+impl Output {
+  pub fn captured() -> Self {
+    Output::Captured(vec![])
+  }
+
+  pub fn print_line(&mut self, line: &str) {
+    match self {
+      Output::Stdout => println!("{}", line),
+      Output::Captured(lines) => lines.extend(line.split('\n').map(str::to_owned)),
+    }
+  }
+
+  pub fn take_lines(&mut self) -> Vec<String> {
+    match self {
+      Output::Stdout => vec![],
+      Output::Captured(lines) => std::mem::take(lines),
+    }
+  }
+}
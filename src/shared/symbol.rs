@@ -0,0 +1,109 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+
+/*
+an interned string. every `Symbol` with the same text shares one allocation, so
+comparing two of them is a pointer comparison and hashing one hashes the pointer,
+never the text. the scanner interns every lexeme and literal, and the tree-walker
+keys its environments, fields and methods on symbols and stores strings as them. the
+vm's constant pools hold names as symbols, and its globals, methods and shapes are
+keyed on them.
+
+the table lives in a thread local. it holds a strong reference to each string and,
+whenever it has doubled in size, drops the strings nobody else holds any more, so
+strings built at runtime don't pile up.
+*/
+
+// pruning a nearly empty table is all overhead.
+const MINIMUM_PRUNE_SIZE: usize = 1024;
+
+thread_local! {
+  static INTERNER: RefCell<Interner> = RefCell::new(Interner::new());
+}
+
+struct Interner {
+  strings: HashSet<Rc<str>>,
+  prune_at: usize,
+}
+
+impl Interner {
+  fn new() -> Self {
+    Interner { strings: HashSet::new(), prune_at: MINIMUM_PRUNE_SIZE }
+  }
+
+  fn intern(&mut self, string: &str) -> Rc<str> {
+    if let Some(interned) = self.strings.get(string) {
+      return Rc::clone(interned);
+    }
+    if self.strings.len() >= self.prune_at {
+      self.strings.retain(|interned| Rc::strong_count(interned) > 1);
+      self.prune_at = MINIMUM_PRUNE_SIZE.max(self.strings.len() * 2);
+    }
+    let interned: Rc<str> = Rc::from(string);
+    self.strings.insert(Rc::clone(&interned));
+    interned
+  }
+}
+
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+impl Symbol {
+  pub fn intern(string: &str) -> Symbol {
+    Symbol(INTERNER.with(|interner| interner.borrow_mut().intern(string)))
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl Deref for Symbol {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    &self.0
+  }
+}
+
+impl PartialEq for Symbol {
+  fn eq(&self, other: &Symbol) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+impl Eq for Symbol {}
+
+impl PartialEq<str> for Symbol {
+  fn eq(&self, other: &str) -> bool {
+    self.as_str() == other
+  }
+}
+
+impl PartialEq<&str> for Symbol {
+  fn eq(&self, other: &&str) -> bool {
+    self.as_str() == *other
+  }
+}
+
+impl Hash for Symbol {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    Rc::as_ptr(&self.0).cast::<u8>().hash(state);
+  }
+}
+
+impl Debug for Symbol {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:?}", self.as_str())
+  }
+}
+
+impl Display for Symbol {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.as_str())
+  }
+}
@@ -0,0 +1,213 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::Diagnostic;
+use crate::pipeline::{self, Outcome};
+use crate::shared::constants::{COMPILE_ERROR_EXIT_CODE, ERROR_EXIT_CODE, RUNTIME_ERROR_EXIT_CODE, SUCCESS_EXIT_CODE};
+use crate::shared::options::RunOptions;
+use crate::shared::output::Output;
+use crate::vm::ASSEMBLY_EXTENSION;
+
+/*
+runs every `.hex` file under a directory and checks it against the annotations
+used by the upstream lox test corpus:
+
+  print 1;           // expect: 1
+  print a;           // expect runtime error: Undefined variable 'a'.
+  print (;           // Error at ';': Expected expression.
+  // [line 3] Error at end: Expected '}' after block.
+
+`[java line N]` annotations are treated like `[line N]`, and `[c line N]` ones are
+ignored, since they describe the other reference implementation.
+
+`.hexasm` files are assembled and always run on the vm; their annotations start with
+`;` instead of `//`, e.g. `print ; expect: 1`.
+*/
+
+const EXPECT_OUTPUT: &str = "expect: ";
+const EXPECT_RUNTIME_ERROR: &str = "expect runtime error: ";
+
+#[derive(Default)]
+struct Expectations {
+  output: Vec<(usize, String)>, // (line of the annotation, expected line of output)
+  compile_errors: Vec<String>,  // formatted as "[line N] Error ...: message"
+  runtime_error: Option<(usize, String)>,
+}
+
+pub fn run_test_suite(directory: &str, options: &RunOptions) -> i32 {
+  let mut files = vec![];
+  if let Err(error) = collect_test_files(Path::new(directory), &mut files) {
+    eprintln!("error: could not read '{}': {}", directory, error);
+    return ERROR_EXIT_CODE;
+  }
+  files.sort();
+
+  let mut passed = 0;
+  let mut failed = 0;
+  for file in &files {
+    let source = match fs::read_to_string(file) {
+      Ok(source) => source,
+      Err(error) => {
+        println!("FAIL {}\n  could not read file: {}", file.display(), error);
+        failed += 1;
+        continue;
+      }
+    };
+    let path_name = file.display().to_string();
+    let failures = match file
+      .extension()
+      .is_some_and(|extension| extension == ASSEMBLY_EXTENSION)
+    {
+      true => check(
+        &parse_expectations(&source, ";"),
+        &pipeline::run_assembly(&source, options, Output::captured()),
+      ),
+      false => check(
+        &parse_expectations(&source, "//"),
+        &pipeline::run_source(&source, &path_name, options, Output::captured()),
+      ),
+    };
+    if failures.is_empty() {
+      passed += 1;
+      continue;
+    }
+    failed += 1;
+    println!("FAIL {}", file.display());
+    for failure in failures {
+      println!("  {}", failure);
+    }
+  }
+
+  println!("\n{} passed, {} failed, {} total", passed, failed, files.len());
+  match failed {
+    0 => SUCCESS_EXIT_CODE,
+    _ => ERROR_EXIT_CODE,
+  }
+}
+
+fn collect_test_files(directory: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+  for entry in fs::read_dir(directory)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      collect_test_files(&path, files)?;
+    } else if path
+      .extension()
+      .is_some_and(|extension| extension == "hex" || extension == ASSEMBLY_EXTENSION)
+    {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
+
+// `comment` is the line comment marker of the file's language.
+fn parse_expectations(source: &str, comment: &str) -> Expectations {
+  let expect_output = format!("{} {}", comment, EXPECT_OUTPUT);
+  let expect_runtime_error = format!("{} {}", comment, EXPECT_RUNTIME_ERROR);
+  let mut expectations = Expectations::default();
+  for (index, text) in source.lines().enumerate() {
+    let line = index + 1;
+    if let Some(position) = text.find(&expect_output) {
+      expectations
+        .output
+        .push((line, text[position + expect_output.len()..].to_owned()));
+      continue;
+    }
+    if let Some(position) = text.find(&expect_runtime_error) {
+      expectations.runtime_error = Some((line, text[position + expect_runtime_error.len()..].to_owned()));
+      continue;
+    }
+    if let Some(error) = parse_compile_error(text, line, comment) {
+      expectations.compile_errors.push(error);
+    }
+  }
+  expectations
+}
+
+// `// Error ...` applies to its own line, `// [line N] Error ...` to line N.
+fn parse_compile_error(text: &str, line: usize, marker: &str) -> Option<String> {
+  let marker = format!("{} ", marker);
+  let comment = &text[text.find(&marker)? + marker.len()..];
+  if comment.starts_with("Error") {
+    return Some(format!("[line {}] {}", line, comment));
+  }
+  let rest = comment.strip_prefix('[')?;
+  let (annotation, error) = rest.split_once("] ")?;
+  if !error.starts_with("Error") {
+    return None;
+  }
+  let line = match annotation.split_whitespace().collect::<Vec<_>>().as_slice() {
+    ["line", line] | ["java", "line", line] => line.parse::<usize>().ok()?,
+    _ => return None,
+  };
+  Some(format!("[line {}] {}", line, error))
+}
+
+fn format_compile_error(diagnostic: &Diagnostic) -> String {
+  match diagnostic.location.as_str() {
+    "" => format!("[line {}] Error: {}", diagnostic.line, diagnostic.message),
+    location => format!("[line {}] Error {}: {}", diagnostic.line, location, diagnostic.message),
+  }
+}
+
+// every mismatch between what the file expects and what actually happened.
+fn check(expectations: &Expectations, outcome: &Outcome) -> Vec<String> {
+  let mut failures = vec![];
+
+  for (index, (line, expected)) in expectations.output.iter().enumerate() {
+    match outcome.output.get(index) {
+      Some(actual) if actual == expected => {}
+      Some(actual) => failures.push(format!(
+        "line {}: expected output '{}', got '{}'",
+        line, expected, actual
+      )),
+      None => failures.push(format!("line {}: missing expected output '{}'", line, expected)),
+    }
+  }
+  for actual in outcome.output.iter().skip(expectations.output.len()) {
+    failures.push(format!("unexpected output '{}'", actual));
+  }
+
+  let compile_errors: Vec<String> = outcome.compile_errors.iter().map(format_compile_error).collect();
+  for expected in &expectations.compile_errors {
+    if !compile_errors.contains(expected) {
+      failures.push(format!("missing expected error: {}", expected));
+    }
+  }
+  for actual in &compile_errors {
+    if !expectations.compile_errors.contains(actual) {
+      failures.push(format!("unexpected error: {}", actual));
+    }
+  }
+
+  let runtime_error = outcome
+    .runtime_error
+    .as_ref()
+    .map(|diagnostic| (diagnostic.line, diagnostic.message.clone()));
+  match (&expectations.runtime_error, &runtime_error) {
+    (Some(expected), Some(actual)) if expected != actual => failures.push(format!(
+      "expected runtime error '{}' at line {}, got '{}' at line {}",
+      expected.1, expected.0, actual.1, actual.0
+    )),
+    (Some(expected), None) => failures.push(format!(
+      "missing expected runtime error '{}' at line {}",
+      expected.1, expected.0
+    )),
+    (None, Some(actual)) => failures.push(format!("unexpected runtime error '{}' at line {}", actual.1, actual.0)),
+    _ => {}
+  }
+
+  let expected_exit_code = match (&expectations.runtime_error, expectations.compile_errors.is_empty()) {
+    (_, false) => COMPILE_ERROR_EXIT_CODE,
+    (Some(_), true) => RUNTIME_ERROR_EXIT_CODE,
+    (None, true) => SUCCESS_EXIT_CODE,
+  };
+  if outcome.exit_code() != expected_exit_code {
+    failures.push(format!(
+      "expected exit code {}, got {}",
+      expected_exit_code,
+      outcome.exit_code()
+    ));
+  }
+  failures
+}
@@ -1,37 +1,56 @@
+use std::cell::RefCell;
 use std::fmt::{Display, Formatter};
+use std::rc::Rc;
 
+use crate::evaluator::{Class, Function, Instance, NativeFunction};
+use crate::shared::symbol::Symbol;
+
+// which values count as false in conditions and `!`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Truthiness {
+  // Lox semantics: only `nil` and `false` are falsy.
+  #[default]
+  Lox,
+  // additionally treats `0` and `""` as falsy.
+  Loose,
+}
+
+#[derive(Clone)]
 pub enum Value {
   Nil,
   Boolean(bool),
   Number(f64),
-  String(String),
+  String(Symbol),
+  Function(Rc<Function>),
+  NativeFunction(Rc<NativeFunction>),
+  Class(Rc<Class>),
+  Instance(Rc<RefCell<Instance>>),
 }
 
 impl Value {
+  #[allow(dead_code)]
   pub fn is_nil(&self) -> bool {
-    match self {
-      Value::Nil => true,
-      _ => false,
-    }
+    matches!(self, Value::Nil)
   }
 
+  #[allow(dead_code)]
   pub fn is_boolean(&self) -> bool {
-    match self {
-      Value::Boolean(_) => true,
-      _ => false,
-    }
+    matches!(self, Value::Boolean(_))
   }
   pub fn is_number(&self) -> bool {
-    match self {
-      Value::Number(_) => true,
-      _ => false,
-    }
+    matches!(self, Value::Number(_))
   }
+  #[allow(dead_code)]
   pub fn is_string(&self) -> bool {
-    match self {
-      Value::String(_) => true,
-      _ => false,
-    }
+    matches!(self, Value::String(_))
+  }
+  #[allow(dead_code)]
+  pub fn is_callable(&self) -> bool {
+    matches!(self, Value::Function(_) | Value::NativeFunction(_) | Value::Class(_))
+  }
+  #[allow(dead_code)]
+  pub fn is_instance(&self) -> bool {
+    matches!(self, Value::Instance(_))
   }
   // create a new value
 
@@ -44,21 +63,73 @@ impl Value {
     Value::Number(number)
   }
   // string
-  pub fn create_string(string: String) -> Value {
+  pub fn create_string(string: Symbol) -> Value {
     Value::String(string)
   }
   // nil
   pub fn create_nil() -> Value {
     Value::Nil
   }
+  // function
+  pub fn create_function(function: Function) -> Value {
+    Value::Function(Rc::new(function))
+  }
+  // native function
+  pub fn create_native_function(function: NativeFunction) -> Value {
+    Value::NativeFunction(Rc::new(function))
+  }
+  // class
+  pub fn create_class(class: Class) -> Value {
+    Value::Class(Rc::new(class))
+  }
+  // instance
+  pub fn create_instance(instance: Instance) -> Value {
+    Value::Instance(Rc::new(RefCell::new(instance)))
+  }
 
   // helper methods
+  #[allow(dead_code)]
   pub fn is_truthy(&self) -> bool {
+    self.is_truthy_with(Truthiness::Lox)
+  }
+
+  pub fn is_truthy_with(&self, truthiness: Truthiness) -> bool {
+    match (self, truthiness) {
+      (Value::Nil, _) => false,
+      (Value::Boolean(boolean), _) => *boolean,
+      (Value::Number(number), Truthiness::Loose) => *number != 0.0,
+      (Value::String(string), Truthiness::Loose) => !string.is_empty(),
+      _ => true,
+    }
+  }
+
+  // values of different types are never equal; numbers follow IEEE 754, so `nan != nan`.
+  // strings are interned symbols, so equal text means the same symbol.
+  // functions, classes and instances are equal only to themselves.
+  pub fn equals(&self, other: &Value) -> bool {
+    match (self, other) {
+      (Value::Nil, Value::Nil) => true,
+      (Value::Boolean(left), Value::Boolean(right)) => left == right,
+      (Value::Number(left), Value::Number(right)) => left == right,
+      (Value::String(left), Value::String(right)) => left == right,
+      (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+      (Value::NativeFunction(left), Value::NativeFunction(right)) => Rc::ptr_eq(left, right),
+      (Value::Class(left), Value::Class(right)) => Rc::ptr_eq(left, right),
+      (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+      _ => false,
+    }
+  }
+
+  #[allow(dead_code)]
+  pub fn type_name(&self) -> &'static str {
     match self {
-      Value::Nil => false,
-      Value::Boolean(boolean) => *boolean,
-      Value::Number(number) => *number != 0.0,
-      Value::String(string) => string.len() > 0,
+      Value::Nil => "nil",
+      Value::Boolean(_) => "boolean",
+      Value::Number(_) => "number",
+      Value::String(_) => "string",
+      Value::Function(_) | Value::NativeFunction(_) => "function",
+      Value::Class(_) => "class",
+      Value::Instance(_) => "instance",
     }
   }
 
@@ -69,12 +140,14 @@ impl Value {
       _ => unreachable!(),
     }
   }
+  #[allow(dead_code)]
   pub fn as_string(&self) -> &str {
     match self {
       Value::String(string) => string.as_str(),
       _ => unreachable!(),
     }
   }
+  #[allow(dead_code)]
   pub fn as_boolean(&self) -> bool {
     match self {
       Value::Boolean(boolean) => *boolean,
@@ -98,7 +171,11 @@ impl Display for Value {
         }
         write!(f, "{}", number_string)
       }
-      Value::String(string) => write!(f, "\"{}\"", string),
+      Value::String(string) => write!(f, "{}", string),
+      Value::Function(function) => write!(f, "<fn {}>", function.name()),
+      Value::NativeFunction(_) => write!(f, "<native fn>"),
+      Value::Class(class) => write!(f, "{}", class.name),
+      Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
     }
   }
 }
@@ -1,3 +1,5 @@
+#[allow(dead_code)] // a debugging aid for printing parsed programs back as source
 pub mod print_visitor;
+#[allow(clippy::module_inception)]
 mod visitor;
 pub use visitor::*;
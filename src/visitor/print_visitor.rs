@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use crate::ast;
 
 use super::Visitor;
@@ -8,48 +10,176 @@ impl Visitor<String> for PrintVisitor {
   fn visit(&mut self, node: &ast::Expression) -> String {
     self.visit_expression(node)
   }
+
+  fn visit_program(&mut self, program: &ast::Program) -> String {
+    let statements: Vec<String> = program
+      .statements
+      .iter()
+      .map(|statement| statement.accept(self))
+      .collect();
+    statements.join("\n")
+  }
+
+  fn visit_statement(&mut self, statement: &ast::Statement) -> String {
+    statement.accept(self)
+  }
+
+  fn visit_print_statement(&mut self, print_statement: &ast::PrintStatement) -> String {
+    format!("print {};", print_statement.expression.accept(self))
+  }
+
+  fn visit_expression_statement(&mut self, expression_statement: &ast::ExpressionStatement) -> String {
+    format!("{};", expression_statement.expression.accept(self))
+  }
+
+  fn visit_variable_statement(&mut self, variable_statement: &ast::VariableStatement) -> String {
+    match &variable_statement.initializer {
+      Some(initializer) => format!("var {} = {};", variable_statement.name.lexeme, initializer.accept(self)),
+      None => format!("var {};", variable_statement.name.lexeme),
+    }
+  }
+
+  fn visit_block_statement(&mut self, block_statement: &ast::BlockStatement) -> String {
+    let statements: Vec<String> = block_statement
+      .statements
+      .iter()
+      .map(|statement| statement.accept(self))
+      .collect();
+    format!("{{ {} }}", statements.join(" "))
+  }
+
+  fn visit_if_statement(&mut self, if_statement: &ast::IfStatement) -> String {
+    let condition = if_statement.condition.accept(self);
+    let then_branch = if_statement.then_branch.accept(self);
+    match &if_statement.else_branch {
+      Some(else_branch) => format!("if ({}) {} else {}", condition, then_branch, else_branch.accept(self)),
+      None => format!("if ({}) {}", condition, then_branch),
+    }
+  }
+
+  fn visit_while_statement(&mut self, while_statement: &ast::WhileStatement) -> String {
+    let condition = while_statement.condition.accept(self);
+    format!("while ({}) {}", condition, while_statement.body.accept(self))
+  }
+
+  fn visit_function_statement(&mut self, function_statement: &Rc<ast::FunctionStatement>) -> String {
+    let params: Vec<&str> = function_statement
+      .params
+      .iter()
+      .map(|param| param.lexeme.as_str())
+      .collect();
+    let body: Vec<String> = function_statement
+      .body
+      .iter()
+      .map(|statement| statement.accept(self))
+      .collect();
+    format!(
+      "fun {}({}) {{ {} }}",
+      function_statement.name.lexeme,
+      params.join(", "),
+      body.join(" ")
+    )
+  }
+
+  fn visit_return_statement(&mut self, return_statement: &ast::ReturnStatement) -> String {
+    match &return_statement.value {
+      Some(value) => format!("return {};", value.accept(self)),
+      None => String::from("return;"),
+    }
+  }
+
+  fn visit_class_statement(&mut self, class_statement: &ast::ClassStatement) -> String {
+    let methods: Vec<String> = class_statement
+      .methods
+      .iter()
+      .map(|method| self.visit_function_statement(method))
+      .collect();
+    let methods: Vec<&str> = methods.iter().map(|method| method.trim_start_matches("fun ")).collect();
+    match &class_statement.superclass {
+      Some(superclass) => format!(
+        "class {} < {} {{ {} }}",
+        class_statement.name.lexeme,
+        superclass.name.lexeme,
+        methods.join(" ")
+      ),
+      None => format!("class {} {{ {} }}", class_statement.name.lexeme, methods.join(" ")),
+    }
+  }
+
   fn visit_binary_expression(&mut self, binary_expression: &ast::BinaryExpression) -> String {
-    binary_expression.left.accept(self)
+    let left = binary_expression.left.accept(self);
+    let right = binary_expression.right.accept(self);
+    format!("{} {} {}", left, binary_expression.operator.lexeme, right)
   }
 
   fn visit_unary_expression(&mut self, unary_expression: &ast::UnaryExpression) -> String {
-    let mut result = String::from("");
     let operator_lexeme = &unary_expression.operator.lexeme;
-    result = format!("{}{}", operator_lexeme, unary_expression.right.accept(self));
-    result
+    format!("{}{}", operator_lexeme, unary_expression.right.accept(self))
+  }
+
+  fn visit_logical_expression(&mut self, logical_expression: &ast::LogicalExpression) -> String {
+    let left = logical_expression.left.accept(self);
+    let right = logical_expression.right.accept(self);
+    format!("{} {} {}", left, logical_expression.operator.lexeme, right)
+  }
+
+  fn visit_call_expression(&mut self, call_expression: &ast::CallExpression) -> String {
+    let arguments: Vec<String> = call_expression
+      .arguments
+      .iter()
+      .map(|argument| argument.accept(self))
+      .collect();
+    format!("{}({})", call_expression.callee.accept(self), arguments.join(", "))
+  }
+
+  fn visit_get_expression(&mut self, get_expression: &ast::GetExpression) -> String {
+    format!("{}.{}", get_expression.object.accept(self), get_expression.name.lexeme)
+  }
+
+  fn visit_set_expression(&mut self, set_expression: &ast::SetExpression) -> String {
+    let object = set_expression.object.accept(self);
+    format!(
+      "{}.{} = {}",
+      object,
+      set_expression.name.lexeme,
+      set_expression.value.accept(self)
+    )
+  }
+
+  fn visit_this_expression(&mut self, _this_expression: &ast::ThisExpression) -> String {
+    String::from("this")
+  }
+
+  fn visit_super_expression(&mut self, super_expression: &ast::SuperExpression) -> String {
+    format!("super.{}", super_expression.method.lexeme)
   }
 
   fn visit_grouping_expression(&mut self, grouping_expression: &ast::GroupingExpression) -> String {
-    let mut result = String::from("");
-    result = format!("({})", grouping_expression.expression.accept(self));
-    return result;
+    format!("({})", grouping_expression.expression.accept(self))
+  }
+
+  fn visit_variable_expression(&mut self, variable_expression: &ast::VariableExpression) -> String {
+    variable_expression.name.lexeme.to_string()
+  }
+
+  fn visit_assignment_expression(&mut self, assignment_expression: &ast::AssignmentExpression) -> String {
+    format!(
+      "{} = {}",
+      assignment_expression.name.lexeme,
+      assignment_expression.value.accept(self)
+    )
   }
 
   fn visit_literal(&mut self, literal: &ast::Literal) -> String {
-    let mut result = String::from("");
     match literal {
-      ast::Literal::StringLiteral(string_literal) => {
-        result = format!("{}", string_literal);
-      }
-      ast::Literal::NumberLiteral(number_literal) => {
-        result = format!("{}", number_literal);
-      }
-      ast::Literal::BooleanLiteral(boolean_literal) => {
-        result = format!("{}", boolean_literal);
-      }
-      ast::Literal::NullLiteral => {
-        result = format!("null");
-      }
+      ast::Literal::StringLiteral(string_literal) => format!("\"{}\"", string_literal),
+      ast::Literal::NumberLiteral(number_literal) => number_literal.to_string(),
+      ast::Literal::BooleanLiteral(boolean_literal) => boolean_literal.to_string(),
+      ast::Literal::NullLiteral => String::from("nil"),
     }
-    return result;
   }
 
   fn visit_expression(&mut self, expression: &ast::Expression) -> String {
-    match expression {
-      ast::Expression::BinaryExpression(binary_expression) => self.visit_binary_expression(binary_expression),
-      ast::Expression::UnaryExpression(unary_expression) => self.visit_unary_expression(unary_expression),
-      ast::Expression::GroupingExpression(grouping_expression) => self.visit_grouping_expression(grouping_expression),
-      ast::Expression::Literal(literal) => self.visit_literal(literal),
-    }
+    expression.accept(self)
   }
 }
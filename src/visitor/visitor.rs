@@ -1,14 +1,56 @@
+use std::rc::Rc;
+
 use crate::ast;
 
 pub trait Visitor<T> {
+  #[allow(dead_code)] // entry point for printing a lone expression
   fn visit(&mut self, ast: &ast::Expression) -> T;
 
+  fn visit_program(&mut self, program: &ast::Program) -> T;
+
+  #[allow(dead_code)]
+  fn visit_statement(&mut self, statement: &ast::Statement) -> T;
+
+  fn visit_print_statement(&mut self, print_statement: &ast::PrintStatement) -> T;
+
+  fn visit_expression_statement(&mut self, expression_statement: &ast::ExpressionStatement) -> T;
+
+  fn visit_variable_statement(&mut self, variable_statement: &ast::VariableStatement) -> T;
+
+  fn visit_block_statement(&mut self, block_statement: &ast::BlockStatement) -> T;
+
+  fn visit_if_statement(&mut self, if_statement: &ast::IfStatement) -> T;
+
+  fn visit_while_statement(&mut self, while_statement: &ast::WhileStatement) -> T;
+
+  fn visit_function_statement(&mut self, function_statement: &Rc<ast::FunctionStatement>) -> T;
+
+  fn visit_return_statement(&mut self, return_statement: &ast::ReturnStatement) -> T;
+
+  fn visit_class_statement(&mut self, class_statement: &ast::ClassStatement) -> T;
+
   fn visit_binary_expression(&mut self, binary_expression: &ast::BinaryExpression) -> T;
 
   fn visit_unary_expression(&mut self, unary_expression: &ast::UnaryExpression) -> T;
 
+  fn visit_logical_expression(&mut self, logical_expression: &ast::LogicalExpression) -> T;
+
+  fn visit_call_expression(&mut self, call_expression: &ast::CallExpression) -> T;
+
+  fn visit_get_expression(&mut self, get_expression: &ast::GetExpression) -> T;
+
+  fn visit_set_expression(&mut self, set_expression: &ast::SetExpression) -> T;
+
+  fn visit_this_expression(&mut self, this_expression: &ast::ThisExpression) -> T;
+
+  fn visit_super_expression(&mut self, super_expression: &ast::SuperExpression) -> T;
+
   fn visit_grouping_expression(&mut self, grouping_expression: &ast::GroupingExpression) -> T;
 
+  fn visit_variable_expression(&mut self, variable_expression: &ast::VariableExpression) -> T;
+
+  fn visit_assignment_expression(&mut self, assignment_expression: &ast::AssignmentExpression) -> T;
+
   fn visit_literal(&mut self, literal: &ast::Literal) -> T;
 
   fn visit_expression(&mut self, expression: &ast::Expression) -> T;
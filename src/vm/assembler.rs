@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::chunk::{Capture, Chunk, Constant, FunctionProto, OpCode, Operand};
+use crate::ast::Span;
+use crate::diagnostics::Diagnostic;
+use crate::shared::symbol::Symbol;
+
+/*
+builds bytecode from a hand-written `.hexasm` file, without going through the parser:
+
+  ; everything after ';' is a comment
+  .const greeting "hello"       ; a named constant, usable from any function
+  .function add 2               ; name and arity; ends at `.end`
+    get_local 1
+    get_local 2
+    add
+    return
+  .end
+  .function counter 0
+    .upvalue local 1            ; captures slot 1 of whichever frame runs `closure counter`
+    get_upvalue 0               ; `.upvalue upvalue N` captures that frame's upvalue N instead
+    return
+  .end
+  closure add                   ; instructions outside a function belong to the script
+  define_global add
+  top:                          ; a label, the target of `jump`, `jump_if_false` and `loop`
+    ...
+
+mnemonics are the disassembler's names, case-insensitive and with an optional `OP_` prefix.
+operands depend on the opcode:
+
+  constant       a number, a "string", or a `.const` name
+  closure        a function declared earlier with `.function`
+  names          an identifier or a "string", e.g. `get_global count`
+  invoke         a method name, then an argument count, e.g. `invoke area 0`
+  slots, counts  an integer from 0 to 255, as are upvalue indexes
+  jumps, loops   a label in the same function
+
+every instruction keeps the line it was written on, so runtime errors point back into the file.
+*/
+
+pub const ASSEMBLY_EXTENSION: &str = "hexasm";
+
+const MAX_CONSTANTS: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+  Word,   // mnemonics, names, labels and directives
+  Number, // a literal, already parsed
+  String, // a literal, without its quotes
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+  kind: TokenKind,
+  text: String,
+  span: Span,
+  line: usize,
+}
+
+// a jump whose label may not have been seen yet.
+struct PendingJump {
+  operand: usize, // offset of the u16 operand in the chunk
+  op: OpCode,
+  label: Token,
+}
+
+// the function currently being assembled.
+struct FunctionState {
+  proto: FunctionProto,
+  labels: HashMap<String, usize>,
+  jumps: Vec<PendingJump>,
+  start: Token, // the `.function` directive, or the end of the file for the script
+}
+
+impl FunctionState {
+  fn new(name: &str, arity: usize, start: Token) -> Self {
+    let mut proto = FunctionProto::new(name);
+    proto.arity = arity;
+    FunctionState { proto, labels: HashMap::new(), jumps: vec![], start }
+  }
+}
+
+pub struct Assembler<'a> {
+  source: &'a str,
+  constants: HashMap<String, Constant>, // from `.const`
+  functions: HashMap<String, Rc<FunctionProto>>,
+  script: FunctionState,
+  current: Option<FunctionState>, // the `.function` being assembled, if any
+  diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Assembler<'a> {
+  pub fn new(source: &'a str) -> Self {
+    let start = Token { kind: TokenKind::Word, text: String::new(), span: Span::new(0, 0), line: 1 };
+    Assembler {
+      source,
+      constants: HashMap::new(),
+      functions: HashMap::new(),
+      script: FunctionState::new("", 0, start),
+      current: None,
+      diagnostics: vec![],
+    }
+  }
+
+  // returns the script function, or every error found.
+  pub fn assemble(&mut self) -> Result<Rc<FunctionProto>, Vec<Diagnostic>> {
+    let source = self.source;
+    let mut offset = 0;
+    for (index, text) in source.split('\n').enumerate() {
+      let tokens = self.tokenize(text, offset, index + 1);
+      offset += text.len() + 1;
+      if !tokens.is_empty() {
+        self.line(&tokens);
+      }
+    }
+    if let Some(function) = self.current.take() {
+      self.error(&function.start, "Expected '.end' after the function body.");
+    }
+    // a script missing its `return` is reported at the last line.
+    self.script.start.line = source.lines().count().max(1);
+    let start = self.script.start.clone();
+    let script = std::mem::replace(&mut self.script, FunctionState::new("", 0, start));
+    let script = self.finish(script);
+    // labels are only checked at the end of their function, so errors arrive out of order.
+    self.diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    match self.diagnostics.is_empty() {
+      true => Ok(Rc::new(script)),
+      false => Err(std::mem::take(&mut self.diagnostics)),
+    }
+  }
+
+  fn tokenize(&mut self, text: &str, line_start: usize, line: usize) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut characters = text.char_indices().peekable();
+    while let Some((start, character)) = characters.next() {
+      if character.is_whitespace() {
+        continue;
+      }
+      if character == ';' {
+        break;
+      }
+      let span_start = line_start + start;
+      if character == '"' {
+        let Some(length) = text[start + 1..].find('"') else {
+          let span = Span::new(span_start, line_start + text.trim_end().len());
+          self
+            .diagnostics
+            .push(Diagnostic::error("Unterminated string.", line, "").with_span(span));
+          return vec![];
+        };
+        let end = start + 1 + length + 1;
+        while characters.peek().is_some_and(|(index, _)| *index < end) {
+          characters.next();
+        }
+        tokens.push(Token {
+          kind: TokenKind::String,
+          text: text[start + 1..end - 1].to_owned(),
+          span: Span::new(span_start, line_start + end),
+          line,
+        });
+        continue;
+      }
+      let mut end = start + character.len_utf8();
+      while let Some((index, next)) = characters.peek() {
+        if next.is_whitespace() || *next == ';' || *next == '"' {
+          break;
+        }
+        end = index + next.len_utf8();
+        characters.next();
+      }
+      let word = &text[start..end];
+      let numeric = word
+        .trim_start_matches('-')
+        .starts_with(|digit: char| digit.is_ascii_digit());
+      let kind = match numeric && word.parse::<f64>().is_ok() {
+        true => TokenKind::Number,
+        false => TokenKind::Word,
+      };
+      tokens.push(Token { kind, text: word.to_owned(), span: Span::new(span_start, line_start + end), line });
+    }
+    tokens
+  }
+
+  fn line(&mut self, tokens: &[Token]) {
+    let first = &tokens[0];
+    match first.text.as_str() {
+      ".const" => return self.const_directive(tokens),
+      ".function" => return self.function_directive(tokens),
+      ".end" => return self.end_directive(tokens),
+      ".upvalue" => return self.upvalue_directive(tokens),
+      _ => {}
+    }
+    if first.kind == TokenKind::Word && first.text.ends_with(':') {
+      self.label(first);
+      if tokens.len() > 1 {
+        self.instruction(&tokens[1..]);
+      }
+      return;
+    }
+    self.instruction(tokens);
+  }
+
+  // .const name value
+  fn const_directive(&mut self, tokens: &[Token]) {
+    let [_, name, value] = tokens else {
+      return self.error(&tokens[0], "Expected '.const name value'.");
+    };
+    if name.kind != TokenKind::Word {
+      return self.error(name, "Expected a constant name.");
+    }
+    let constant = match value.kind {
+      TokenKind::Number => Constant::Number(value.text.parse().unwrap()),
+      TokenKind::String => Constant::String(Symbol::intern(&value.text)),
+      TokenKind::Word => return self.error(value, "Expected a number or a string."),
+    };
+    if self.constants.insert(name.text.clone(), constant).is_some() {
+      self.error(name, "Already a constant with this name.");
+    }
+  }
+
+  // .function name arity
+  // a malformed directive still opens a function, so its body and `.end` don't cascade into more errors.
+  fn function_directive(&mut self, tokens: &[Token]) {
+    if self.current.is_some() {
+      return self.error(&tokens[0], "Functions can't be nested; expected '.end' first.");
+    }
+    let directive = &tokens[0];
+    let (name, arity) = match tokens {
+      [_, name, arity] if name.kind == TokenKind::Word => (name.text.as_str(), self.small_integer(arity).unwrap_or(0)),
+      [_, name, _] => {
+        self.error(name, "Expected a function name.");
+        ("", 0)
+      }
+      _ => {
+        self.error(directive, "Expected '.function name arity'.");
+        ("", 0)
+      }
+    };
+    self.current = Some(FunctionState::new(name, arity, directive.clone()));
+  }
+
+  fn end_directive(&mut self, tokens: &[Token]) {
+    if tokens.len() > 1 {
+      return self.error(&tokens[1], "Expected end of line after '.end'.");
+    }
+    let Some(function) = self.current.take() else {
+      return self.error(&tokens[0], "'.end' outside of a function.");
+    };
+    let start = function.start.clone();
+    let proto = self.finish(function);
+    if proto.name.is_empty() {
+      return; // a malformed `.function`, already reported
+    }
+    if self.functions.insert(proto.name.clone(), Rc::new(proto)).is_some() {
+      self.error(&start, "Already a function with this name.");
+    }
+  }
+
+  // .upvalue local slot | .upvalue upvalue index
+  fn upvalue_directive(&mut self, tokens: &[Token]) {
+    let [directive, kind, index] = tokens else {
+      return self.error(
+        &tokens[0],
+        "Expected '.upvalue local slot' or '.upvalue upvalue index'.",
+      );
+    };
+    let Some(function) = self.current.as_ref() else {
+      return self.error(directive, "'.upvalue' outside of a function.");
+    };
+    if !function.proto.chunk.code.is_empty() {
+      return self.error(directive, "Upvalues must be declared before the first instruction.");
+    }
+    let Some(index) = self.small_integer(index) else {
+      return;
+    };
+    let capture = match kind.text.as_str() {
+      "local" => Capture::Local(index as u8),
+      "upvalue" => Capture::Upvalue(index as u8),
+      _ => return self.error(kind, "Expected 'local' or 'upvalue'."),
+    };
+    self.state().proto.upvalues.push(capture);
+  }
+
+  fn label(&mut self, token: &Token) {
+    let name = token.text.trim_end_matches(':').to_owned();
+    let state = self.state();
+    let offset = state.proto.chunk.code.len();
+    if state.labels.insert(name, offset).is_some() {
+      self.error(token, "Already a label with this name in this function.");
+    }
+  }
+
+  fn instruction(&mut self, tokens: &[Token]) {
+    let mnemonic = &tokens[0];
+    let Some(op) = opcode(&mnemonic.text) else {
+      return self.error(mnemonic, "Unknown instruction.");
+    };
+    let operands = &tokens[1..];
+    match (op.operand(), operands) {
+      (Operand::None, []) => self.emit(op as u8, mnemonic.line),
+      (Operand::None, [extra, ..]) => self.error(extra, "This instruction takes no operand."),
+      (Operand::Invoke, [name, count]) => {
+        let (Some(name), Some(count)) = (self.name_operand(name), self.small_integer(count)) else {
+          return;
+        };
+        self.emit(op as u8, mnemonic.line);
+        self.emit(name, mnemonic.line);
+        self.emit(count as u8, mnemonic.line);
+      }
+      (Operand::Invoke, _) => self.error(mnemonic, "Expected a method name and an argument count."),
+      (_, []) => self.error(mnemonic, "Expected an operand."),
+      (_, [_, extra, ..]) => self.error(extra, "Expected end of line after the operand."),
+      (operand, [argument]) => {
+        let byte = match operand {
+          Operand::Constant => self.constant_operand(op, argument),
+          Operand::Name => self.name_operand(argument),
+          Operand::Slot | Operand::Upvalue | Operand::ArgumentCount => {
+            self.small_integer(argument).map(|value| value as u8)
+          }
+          Operand::Jump | Operand::Loop => {
+            let state = self.state();
+            let operand = state.proto.chunk.code.len() + 1;
+            state.jumps.push(PendingJump { operand, op, label: argument.clone() });
+            self.emit(op as u8, mnemonic.line);
+            self.emit(0xff, mnemonic.line);
+            self.emit(0xff, mnemonic.line);
+            return;
+          }
+          Operand::None | Operand::Invoke => unreachable!("handled above"),
+        };
+        if let Some(byte) = byte {
+          self.emit(op as u8, mnemonic.line);
+          self.emit(byte, mnemonic.line);
+        }
+      }
+    }
+  }
+
+  fn constant_operand(&mut self, op: OpCode, token: &Token) -> Option<u8> {
+    let constant = match (op, &token.kind) {
+      (OpCode::Closure, TokenKind::Word) => match self.functions.get(&token.text) {
+        Some(function) => Constant::Function(Rc::clone(function)),
+        None => {
+          self.error(
+            token,
+            "Undefined function; declare it with '.function' before using it.",
+          );
+          return None;
+        }
+      },
+      (OpCode::Closure, _) => {
+        self.error(token, "Expected a function name.");
+        return None;
+      }
+      (_, TokenKind::Number) => Constant::Number(token.text.parse().unwrap()),
+      (_, TokenKind::String) => Constant::String(Symbol::intern(&token.text)),
+      (_, TokenKind::Word) => match self.constants.get(&token.text) {
+        Some(constant) => constant.clone(),
+        None => {
+          self.error(token, "Undefined constant; declare it with '.const'.");
+          return None;
+        }
+      },
+    };
+    self.add_constant(constant, token)
+  }
+
+  fn name_operand(&mut self, token: &Token) -> Option<u8> {
+    if token.kind == TokenKind::Number {
+      self.error(token, "Expected a name.");
+      return None;
+    }
+    self.add_constant(Constant::String(Symbol::intern(&token.text)), token)
+  }
+
+  fn add_constant(&mut self, constant: Constant, token: &Token) -> Option<u8> {
+    let index = self.state().proto.chunk.add_constant(constant);
+    if index >= MAX_CONSTANTS {
+      self.error(token, "Too many constants in one chunk.");
+      return None;
+    }
+    Some(index as u8)
+  }
+
+  fn small_integer(&mut self, token: &Token) -> Option<usize> {
+    match token.text.parse::<u8>() {
+      Ok(value) if token.kind == TokenKind::Number => Some(value as usize),
+      _ => {
+        self.error(token, "Expected an integer from 0 to 255.");
+        None
+      }
+    }
+  }
+
+  // patches every jump now that all labels are known.
+  fn finish(&mut self, mut function: FunctionState) -> FunctionProto {
+    for jump in std::mem::take(&mut function.jumps) {
+      let name = &jump.label.text;
+      let Some(target) = function.labels.get(name).copied() else {
+        self.error(&jump.label, "Undefined label.");
+        continue;
+      };
+      let after = jump.operand + 2;
+      let distance = match jump.op {
+        OpCode::Loop if target <= after => after - target,
+        OpCode::Loop => {
+          self.error(&jump.label, "'loop' can only jump backward.");
+          continue;
+        }
+        _ if target >= after => target - after,
+        _ => {
+          self.error(&jump.label, "Jumps can only go forward; use 'loop' to go back.");
+          continue;
+        }
+      };
+      if distance > u16::MAX as usize {
+        self.error(&jump.label, "Too much code to jump over.");
+        continue;
+      }
+      let [high, low] = (distance as u16).to_be_bytes();
+      function.proto.chunk.code[jump.operand] = high;
+      function.proto.chunk.code[jump.operand + 1] = low;
+    }
+    let ends_in_transfer = matches!(
+      last_opcode(&function.proto.chunk),
+      Some(OpCode::Return | OpCode::Jump | OpCode::Loop)
+    );
+    if !ends_in_transfer {
+      self.error(
+        &function.start,
+        "Expected the function to end with 'return', 'jump' or 'loop'.",
+      );
+    }
+    function.proto
+  }
+
+  fn state(&mut self) -> &mut FunctionState {
+    self.current.as_mut().unwrap_or(&mut self.script)
+  }
+
+  fn emit(&mut self, byte: u8, line: usize) {
+    self.state().proto.chunk.write(byte, line);
+  }
+
+  fn error(&mut self, token: &Token, message: &str) {
+    let diagnostic = match token.text.is_empty() {
+      true => Diagnostic::error(message, token.line, "at end"),
+      false => {
+        Diagnostic::error(message, token.line, &format!("at '{}'", token.span.text(self.source))).with_span(token.span)
+      }
+    };
+    self.diagnostics.push(diagnostic);
+  }
+}
+
+// accepts `OP_GET_LOCAL`, `get_local` and anything in between.
+fn opcode(mnemonic: &str) -> Option<OpCode> {
+  let upper = mnemonic.to_uppercase();
+  let name = match upper.starts_with("OP_") {
+    true => upper,
+    false => format!("OP_{}", upper),
+  };
+  OpCode::ALL.iter().copied().find(|op| op.name() == name)
+}
+
+// walks the instructions, since the last byte may be an operand.
+fn last_opcode(chunk: &Chunk) -> Option<OpCode> {
+  let mut offset = 0;
+  let mut last = None;
+  while offset < chunk.code.len() {
+    let op = OpCode::from_byte(chunk.code[offset])?;
+    last = Some(op);
+    offset += op.size();
+  }
+  last
+}
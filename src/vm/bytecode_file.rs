@@ -0,0 +1,259 @@
+use std::rc::Rc;
+
+use super::chunk::{Capture, Chunk, Constant, FunctionProto, MAX_FUNCTION_DEPTH};
+use crate::diagnostics::Diagnostic;
+use crate::shared::symbol::Symbol;
+
+/*
+the layout `lox compile` writes, with every integer little-endian:
+
+  file      → "HEXC" version:u16 source_hash:u64 function
+  function  → name arity:u32 chunk upvalue_count:u32 upvalue*
+  chunk     → code:bytes lines:u32[code length] constant_count:u32 constant*
+  constant  → 0 number:f64 | 1 string | 2 function
+  upvalue   → 0 slot:u8 | 1 index:u8
+  name      → string
+  string    → length:u32 utf-8 bytes
+  bytes     → length:u32 raw bytes
+
+an upvalue comes from a local slot of the enclosing frame (0) or from one of its own upvalues (1).
+`source_hash` is the fnv-1a hash of the source text, so a stale file can be told apart from its source.
+*/
+
+pub const BYTECODE_EXTENSION: &str = "hexc";
+pub const BYTECODE_MAGIC: &[u8; 4] = b"HEXC";
+// bump whenever the layout or the meaning of an opcode changes.
+pub const BYTECODE_VERSION: u16 = 3;
+
+const NUMBER_TAG: u8 = 0;
+const STRING_TAG: u8 = 1;
+const FUNCTION_TAG: u8 = 2;
+
+const LOCAL_TAG: u8 = 0;
+const UPVALUE_TAG: u8 = 1;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+pub struct BytecodeFile {
+  pub source_hash: u64,
+  pub script: Rc<FunctionProto>,
+}
+
+pub fn source_hash(source: &str) -> u64 {
+  source.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+    (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+  })
+}
+
+pub fn encode(script: &FunctionProto, source: &str) -> Vec<u8> {
+  let mut bytes = BYTECODE_MAGIC.to_vec();
+  bytes.extend_from_slice(&BYTECODE_VERSION.to_le_bytes());
+  bytes.extend_from_slice(&source_hash(source).to_le_bytes());
+  write_function(&mut bytes, script);
+  bytes
+}
+
+fn write_function(bytes: &mut Vec<u8>, function: &FunctionProto) {
+  write_string(bytes, &function.name);
+  write_u32(bytes, function.arity as u32);
+  write_chunk(bytes, &function.chunk);
+  write_u32(bytes, function.upvalues.len() as u32);
+  for capture in &function.upvalues {
+    match capture {
+      Capture::Local(slot) => bytes.extend_from_slice(&[LOCAL_TAG, *slot]),
+      Capture::Upvalue(index) => bytes.extend_from_slice(&[UPVALUE_TAG, *index]),
+    }
+  }
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, chunk: &Chunk) {
+  write_u32(bytes, chunk.code.len() as u32);
+  bytes.extend_from_slice(&chunk.code);
+  for line in &chunk.lines {
+    write_u32(bytes, *line as u32);
+  }
+  write_u32(bytes, chunk.constants.len() as u32);
+  for constant in &chunk.constants {
+    match constant {
+      Constant::Number(number) => {
+        bytes.push(NUMBER_TAG);
+        bytes.extend_from_slice(&number.to_le_bytes());
+      }
+      Constant::String(string) => {
+        bytes.push(STRING_TAG);
+        write_string(bytes, string);
+      }
+      Constant::Function(function) => {
+        bytes.push(FUNCTION_TAG);
+        write_function(bytes, function);
+      }
+    }
+  }
+}
+
+fn write_string(bytes: &mut Vec<u8>, string: &str) {
+  write_u32(bytes, string.len() as u32);
+  bytes.extend_from_slice(string.as_bytes());
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+  bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+// why a `.hexc` file could not be loaded; kept small since every read returns one.
+#[derive(Debug)]
+pub struct DecodeError {
+  pub message: String,
+  pub notes: Vec<String>,
+}
+
+impl DecodeError {
+  fn new(message: &str) -> Self {
+    DecodeError { message: message.to_owned(), notes: vec![] }
+  }
+
+  fn with_note(mut self, note: &str) -> Self {
+    self.notes.push(note.to_owned());
+    self
+  }
+
+  // a binary file has no lines to point at, so everything goes into notes.
+  pub fn to_diagnostic(&self) -> Diagnostic {
+    let mut diagnostic = Diagnostic::error(&self.message, 0, "");
+    for note in &self.notes {
+      diagnostic = diagnostic.with_note(note);
+    }
+    diagnostic
+  }
+}
+
+// the header is checked before anything else, so an old or foreign file fails with a useful message.
+pub fn decode(bytes: &[u8]) -> Result<BytecodeFile, DecodeError> {
+  let mut reader = Reader { bytes, offset: 0, depth: 0 };
+  if !bytes.starts_with(BYTECODE_MAGIC) {
+    return Err(DecodeError::new("Not a lox bytecode file.").with_note("bytecode files start with \"HEXC\""));
+  }
+  reader.offset = BYTECODE_MAGIC.len();
+  let version = reader.read_u16()?;
+  if version != BYTECODE_VERSION {
+    return Err(
+      DecodeError::new(&format!("Unsupported bytecode version {}.", version))
+        .with_note(&format!("this build of lox reads version {}", BYTECODE_VERSION))
+        .with_note("recompile the source with `lox compile`"),
+    );
+  }
+  let source_hash = reader.read_u64()?;
+  let script = Rc::new(reader.read_function()?);
+  if reader.offset != bytes.len() {
+    return Err(reader.error("Unexpected data after the script."));
+  }
+  Ok(BytecodeFile { source_hash, script })
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  offset: usize,
+  depth: usize, // how many functions enclose the one being read
+}
+
+impl<'a> Reader<'a> {
+  fn error(&self, message: &str) -> DecodeError {
+    DecodeError::new(message).with_note(&format!("at byte offset {}", self.offset))
+  }
+
+  fn take(&mut self, length: usize) -> Result<&'a [u8], DecodeError> {
+    let end = self.offset.checked_add(length).filter(|end| *end <= self.bytes.len());
+    let Some(end) = end else {
+      return Err(self.error("Truncated bytecode file."));
+    };
+    let slice = &self.bytes[self.offset..end];
+    self.offset = end;
+    Ok(slice)
+  }
+
+  fn read_u8(&mut self) -> Result<u8, DecodeError> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn read_u16(&mut self) -> Result<u16, DecodeError> {
+    Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+  }
+
+  fn read_u32(&mut self) -> Result<u32, DecodeError> {
+    Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+  }
+
+  fn read_u64(&mut self) -> Result<u64, DecodeError> {
+    Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+  }
+
+  fn read_string(&mut self) -> Result<String, DecodeError> {
+    let length = self.read_u32()? as usize;
+    let start = self.offset;
+    let bytes = self.take(length)?;
+    match std::str::from_utf8(bytes) {
+      Ok(string) => Ok(string.to_owned()),
+      Err(_) => {
+        self.offset = start;
+        Err(self.error("Invalid utf-8 in a bytecode string."))
+      }
+    }
+  }
+
+  fn read_function(&mut self) -> Result<FunctionProto, DecodeError> {
+    if self.depth == MAX_FUNCTION_DEPTH {
+      return Err(self.error("Functions nested too deeply."));
+    }
+    self.depth += 1;
+    let function = self.read_function_body();
+    self.depth -= 1;
+    function
+  }
+
+  fn read_function_body(&mut self) -> Result<FunctionProto, DecodeError> {
+    let name = self.read_string()?;
+    let arity = self.read_u32()? as usize;
+    let chunk = self.read_chunk()?;
+    let count = self.read_u32()? as usize;
+    let mut upvalues = vec![];
+    for _ in 0..count {
+      let tag_offset = self.offset;
+      let capture = match self.read_u8()? {
+        LOCAL_TAG => Capture::Local(self.read_u8()?),
+        UPVALUE_TAG => Capture::Upvalue(self.read_u8()?),
+        tag => {
+          self.offset = tag_offset;
+          return Err(self.error(&format!("Unknown upvalue tag {}.", tag)));
+        }
+      };
+      upvalues.push(capture);
+    }
+    Ok(FunctionProto { name, arity, upvalues, chunk })
+  }
+
+  fn read_chunk(&mut self) -> Result<Chunk, DecodeError> {
+    let length = self.read_u32()? as usize;
+    let code = self.take(length)?.to_vec();
+    let mut lines = Vec::with_capacity(length);
+    for _ in 0..length {
+      lines.push(self.read_u32()? as usize);
+    }
+    let count = self.read_u32()? as usize;
+    let mut constants = vec![];
+    for _ in 0..count {
+      let tag_offset = self.offset;
+      let constant = match self.read_u8()? {
+        NUMBER_TAG => Constant::Number(f64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+        STRING_TAG => Constant::String(Symbol::intern(&self.read_string()?)),
+        FUNCTION_TAG => Constant::Function(Rc::new(self.read_function()?)),
+        tag => {
+          self.offset = tag_offset;
+          return Err(self.error(&format!("Unknown constant tag {}.", tag)));
+        }
+      };
+      constants.push(constant);
+    }
+    Ok(Chunk { code, lines, constants, spans: vec![] })
+  }
+}
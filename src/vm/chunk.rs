@@ -0,0 +1,306 @@
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use crate::ast::Span;
+use crate::shared::symbol::Symbol;
+
+// one byte per instruction; operands follow inline as described next to each opcode.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+  Constant,     // constant index (u8)
+  Nil,          //
+  True,         //
+  False,        //
+  Pop,          //
+  GetLocal,     // stack slot (u8)
+  SetLocal,     // stack slot (u8)
+  GetGlobal,    // name constant (u8)
+  DefineGlobal, // name constant (u8)
+  SetGlobal,    // name constant (u8)
+  GetUpvalue,   // upvalue index (u8)
+  SetUpvalue,   // upvalue index (u8)
+  GetProperty,  // name constant (u8)
+  SetProperty,  // name constant (u8)
+  GetSuper,     // name constant (u8)
+  Equal,        //
+  Greater,      //
+  GreaterEqual, //
+  Less,         //
+  LessEqual,    //
+  Add,          //
+  Subtract,     //
+  Multiply,     //
+  Divide,       //
+  Not,          //
+  Negate,       //
+  Print,        //
+  Jump,         // forward offset (u16)
+  JumpIfFalse,  // forward offset (u16), leaves the condition on the stack
+  Loop,         // backward offset (u16)
+  Call,         // argument count (u8)
+  Invoke,       // method name constant (u8), argument count (u8)
+  Closure,      // function constant (u8), captures as listed in the function's `upvalues`
+  CloseUpvalue, //
+  Return,       //
+  Class,        // name constant (u8)
+  Inherit,      //
+  Method,       // name constant (u8)
+}
+
+impl OpCode {
+  pub const ALL: [OpCode; 38] = [
+    OpCode::Constant,
+    OpCode::Nil,
+    OpCode::True,
+    OpCode::False,
+    OpCode::Pop,
+    OpCode::GetLocal,
+    OpCode::SetLocal,
+    OpCode::GetGlobal,
+    OpCode::DefineGlobal,
+    OpCode::SetGlobal,
+    OpCode::GetUpvalue,
+    OpCode::SetUpvalue,
+    OpCode::GetProperty,
+    OpCode::SetProperty,
+    OpCode::GetSuper,
+    OpCode::Equal,
+    OpCode::Greater,
+    OpCode::GreaterEqual,
+    OpCode::Less,
+    OpCode::LessEqual,
+    OpCode::Add,
+    OpCode::Subtract,
+    OpCode::Multiply,
+    OpCode::Divide,
+    OpCode::Not,
+    OpCode::Negate,
+    OpCode::Print,
+    OpCode::Jump,
+    OpCode::JumpIfFalse,
+    OpCode::Loop,
+    OpCode::Call,
+    OpCode::Invoke,
+    OpCode::Closure,
+    OpCode::CloseUpvalue,
+    OpCode::Return,
+    OpCode::Class,
+    OpCode::Inherit,
+    OpCode::Method,
+  ];
+
+  pub fn from_byte(byte: u8) -> Option<OpCode> {
+    OpCode::ALL.get(byte as usize).copied()
+  }
+
+  // the mnemonic used by the disassembler, e.g. "OP_GET_LOCAL".
+  pub fn name(self) -> &'static str {
+    match self {
+      OpCode::Constant => "OP_CONSTANT",
+      OpCode::Nil => "OP_NIL",
+      OpCode::True => "OP_TRUE",
+      OpCode::False => "OP_FALSE",
+      OpCode::Pop => "OP_POP",
+      OpCode::GetLocal => "OP_GET_LOCAL",
+      OpCode::SetLocal => "OP_SET_LOCAL",
+      OpCode::GetGlobal => "OP_GET_GLOBAL",
+      OpCode::DefineGlobal => "OP_DEFINE_GLOBAL",
+      OpCode::SetGlobal => "OP_SET_GLOBAL",
+      OpCode::GetUpvalue => "OP_GET_UPVALUE",
+      OpCode::SetUpvalue => "OP_SET_UPVALUE",
+      OpCode::GetProperty => "OP_GET_PROPERTY",
+      OpCode::SetProperty => "OP_SET_PROPERTY",
+      OpCode::GetSuper => "OP_GET_SUPER",
+      OpCode::Equal => "OP_EQUAL",
+      OpCode::Greater => "OP_GREATER",
+      OpCode::GreaterEqual => "OP_GREATER_EQUAL",
+      OpCode::Less => "OP_LESS",
+      OpCode::LessEqual => "OP_LESS_EQUAL",
+      OpCode::Add => "OP_ADD",
+      OpCode::Subtract => "OP_SUBTRACT",
+      OpCode::Multiply => "OP_MULTIPLY",
+      OpCode::Divide => "OP_DIVIDE",
+      OpCode::Not => "OP_NOT",
+      OpCode::Negate => "OP_NEGATE",
+      OpCode::Print => "OP_PRINT",
+      OpCode::Jump => "OP_JUMP",
+      OpCode::JumpIfFalse => "OP_JUMP_IF_FALSE",
+      OpCode::Loop => "OP_LOOP",
+      OpCode::Call => "OP_CALL",
+      OpCode::Invoke => "OP_INVOKE",
+      OpCode::Closure => "OP_CLOSURE",
+      OpCode::CloseUpvalue => "OP_CLOSE_UPVALUE",
+      OpCode::Return => "OP_RETURN",
+      OpCode::Class => "OP_CLASS",
+      OpCode::Inherit => "OP_INHERIT",
+      OpCode::Method => "OP_METHOD",
+    }
+  }
+
+  pub fn operand(self) -> Operand {
+    match self {
+      OpCode::Constant | OpCode::Closure => Operand::Constant,
+      OpCode::GetGlobal
+      | OpCode::DefineGlobal
+      | OpCode::SetGlobal
+      | OpCode::GetProperty
+      | OpCode::SetProperty
+      | OpCode::GetSuper
+      | OpCode::Class
+      | OpCode::Method => Operand::Name,
+      OpCode::GetLocal | OpCode::SetLocal => Operand::Slot,
+      OpCode::GetUpvalue | OpCode::SetUpvalue => Operand::Upvalue,
+      OpCode::Call => Operand::ArgumentCount,
+      OpCode::Invoke => Operand::Invoke,
+      OpCode::Jump | OpCode::JumpIfFalse => Operand::Jump,
+      OpCode::Loop => Operand::Loop,
+      _ => Operand::None,
+    }
+  }
+
+  // the opcode byte plus its operand bytes.
+  pub fn size(self) -> usize {
+    match self.operand() {
+      Operand::None => 1,
+      Operand::Jump | Operand::Loop | Operand::Invoke => 3,
+      _ => 2,
+    }
+  }
+}
+
+// what follows an opcode in the instruction stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+  None,
+  Constant,      // constant index (u8)
+  Name,          // index of a string constant (u8)
+  Slot,          // stack slot relative to the frame (u8)
+  Upvalue,       // index into the running closure's upvalues (u8)
+  ArgumentCount, // (u8)
+  Jump,          // forward offset (u16)
+  Loop,          // backward offset (u16)
+  Invoke,        // index of a string constant (u8), then an argument count (u8)
+}
+
+// a compile-time value stored in a chunk's constant pool.
+#[derive(Debug, Clone)]
+pub enum Constant {
+  Number(f64),
+  String(Symbol), // interned, so the vm can key globals, methods and fields on it without copying
+  Function(Rc<FunctionProto>),
+}
+
+impl Display for Constant {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Constant::Number(number) => write!(f, "{}", crate::values::Value::Number(*number)),
+      Constant::String(string) => write!(f, "{:?}", string),
+      Constant::Function(function) => write!(f, "{}", function),
+    }
+  }
+}
+
+// where a new closure finds one of the variables it captures, relative to the function creating it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+  Local(u8),   // a stack slot of the creating function's frame
+  Upvalue(u8), // one of the creating closure's own upvalues
+}
+
+impl Display for Capture {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Capture::Local(slot) => write!(f, "local {}", slot),
+      Capture::Upvalue(index) => write!(f, "upvalue {}", index),
+    }
+  }
+}
+
+// how deeply functions may nest inside each other's constants. decoding and verifying recurse
+// once per level, so a crafted file could otherwise overflow the stack before anything runs.
+pub const MAX_FUNCTION_DEPTH: usize = 256;
+
+// a compiled function body; the top-level script is one too, with an empty name.
+// `upvalues` lists what `OP_CLOSURE` captures, in the order `OP_GET_UPVALUE` indexes them.
+#[derive(Debug, Clone)]
+pub struct FunctionProto {
+  pub name: String,
+  pub arity: usize,
+  pub upvalues: Vec<Capture>,
+  pub chunk: Chunk,
+}
+
+impl Display for FunctionProto {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self.name.as_str() {
+      "" => write!(f, "<script>"),
+      name => write!(f, "<fn {}>", name),
+    }
+  }
+}
+
+impl FunctionProto {
+  pub fn new(name: &str) -> Self {
+    FunctionProto { name: name.to_owned(), arity: 0, upvalues: vec![], chunk: Chunk::new() }
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+  pub code: Vec<u8>,
+  pub lines: Vec<usize>, // source line of every byte in `code`
+  pub constants: Vec<Constant>,
+  // (instruction offset, span) for the instructions that can fail at runtime, in offset order.
+  // only the compiler knows spans; decoded and assembled chunks have none.
+  pub spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+  pub fn new() -> Self {
+    Chunk::default()
+  }
+
+  pub fn write(&mut self, byte: u8, line: usize) {
+    self.code.push(byte);
+    self.lines.push(line);
+  }
+
+  pub fn write_op(&mut self, op: OpCode, line: usize) {
+    self.write(op as u8, line);
+  }
+
+  // `span` is what a runtime error in the next instruction written points at.
+  pub fn mark_span(&mut self, span: Span) {
+    self.spans.push((self.code.len(), span));
+  }
+
+  // the span of the instruction that `offset` is part of, if it has one.
+  pub fn span_at(&self, offset: usize) -> Option<Span> {
+    let index = self
+      .spans
+      .partition_point(|(start, _)| *start <= offset)
+      .checked_sub(1)?;
+    let (start, span) = self.spans[index];
+    let op = OpCode::from_byte(self.code[start])?;
+    (offset < start + op.size()).then_some(span)
+  }
+
+  // numbers and strings already in the pool are reused; functions never are.
+  pub fn add_constant(&mut self, constant: Constant) -> usize {
+    let existing = self.constants.iter().position(|other| match (other, &constant) {
+      (Constant::Number(left), Constant::Number(right)) => left.to_bits() == right.to_bits(),
+      (Constant::String(left), Constant::String(right)) => left == right,
+      _ => false,
+    });
+    if let Some(index) = existing {
+      return index;
+    }
+    self.constants.push(constant);
+    self.constants.len() - 1
+  }
+
+  pub fn read_u16(&self, offset: usize) -> u16 {
+    u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+  }
+}
@@ -0,0 +1,627 @@
+use std::rc::Rc;
+
+use super::chunk::{Capture, Chunk, Constant, FunctionProto, OpCode, MAX_FUNCTION_DEPTH};
+use crate::ast;
+use crate::ast::tokens::{Token, TokenEnum};
+use crate::ast::Span;
+use crate::diagnostics::error_handler::ErrorHandler;
+use crate::diagnostics::Diagnostic;
+use crate::parser::MAX_ARGUMENTS;
+use crate::shared::symbol::Symbol;
+use crate::visitor::Visitor;
+
+/*
+lowers a resolved program to bytecode. the resolver has already reported the static
+errors (duplicate locals, `return` at top level, misplaced `this`/`super`...), so the
+only errors left here are the limits of the instruction encoding.
+
+locals live in stack slots; slot 0 of every call frame holds the callee, or the
+receiver for methods. globals are looked up by name at runtime.
+
+a local of an enclosing function is reached through an upvalue: the function records
+where to capture it from (a local of the function just outside it, or one of that
+function's own upvalues), and the local is marked so that leaving its scope closes
+the upvalue instead of just popping the slot.
+*/
+
+const MAX_LOCALS: usize = 256;
+const MAX_CONSTANTS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+  Script,
+  Function,
+  Initializer,
+  Method,
+}
+
+struct Local {
+  name: String,
+  depth: usize,
+  is_captured: bool, // by a closure, which needs the slot moved to the heap when it goes out of scope
+}
+
+// the function currently being compiled; nested declarations push a new one.
+struct FunctionState {
+  proto: FunctionProto,
+  kind: FunctionKind,
+  locals: Vec<Local>,
+  upvalues: Vec<Capture>,
+  scope_depth: usize,
+}
+
+impl FunctionState {
+  fn new(name: &str, kind: FunctionKind) -> Self {
+    // slot 0 is named so that only methods can refer to it, as `this`.
+    let receiver = match kind {
+      FunctionKind::Initializer | FunctionKind::Method => "this",
+      FunctionKind::Script | FunctionKind::Function => "",
+    };
+    FunctionState {
+      proto: FunctionProto::new(name),
+      kind,
+      locals: vec![Local { name: receiver.to_owned(), depth: 0, is_captured: false }],
+      upvalues: vec![],
+      scope_depth: 0,
+    }
+  }
+}
+
+pub struct Compiler<'a> {
+  source: &'a str,
+  line_starts: Vec<usize>, // byte offset where every line begins, to map spans to lines
+  functions: Vec<FunctionState>,
+  diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Compiler<'a> {
+  pub fn new(source: &'a str) -> Self {
+    let line_starts = std::iter::once(0)
+      .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+      .collect();
+    Compiler { source, line_starts, functions: vec![], diagnostics: vec![] }
+  }
+
+  // returns the top-level script function, or every error found.
+  pub fn compile(&mut self, program: &ast::Program) -> Result<Rc<FunctionProto>, Vec<Diagnostic>> {
+    self.functions.push(FunctionState::new("", FunctionKind::Script));
+    program.accept(self);
+    let line = self.line_starts.len();
+    self.emit_return(line);
+    let script = self.functions.pop().expect("the script function is always present");
+    if !self.diagnostics.is_empty() {
+      let mut errors = std::mem::take(&mut self.diagnostics);
+      errors.sort_by_key(|error| error.line);
+      return Err(errors);
+    }
+    Ok(Rc::new(script.proto))
+  }
+
+  fn current(&mut self) -> &mut FunctionState {
+    self.functions.last_mut().expect("compiling outside of any function")
+  }
+
+  fn chunk(&mut self) -> &mut Chunk {
+    &mut self.current().proto.chunk
+  }
+
+  fn line_of(&self, span: Span) -> usize {
+    self.line_starts.partition_point(|&start| start <= span.start)
+  }
+
+  // emitting
+
+  fn emit_op(&mut self, op: OpCode, line: usize) {
+    self.chunk().write_op(op, line);
+  }
+
+  fn emit_op_with_operand(&mut self, op: OpCode, operand: u8, line: usize) {
+    self.chunk().write_op(op, line);
+    self.chunk().write(operand, line);
+  }
+
+  fn emit_constant(&mut self, constant: Constant, span: Span) {
+    let index = self.make_constant(constant, span);
+    self.emit_op_with_operand(OpCode::Constant, index, self.line_of(span));
+  }
+
+  fn make_constant(&mut self, constant: Constant, span: Span) -> u8 {
+    let index = self.chunk().add_constant(constant);
+    if index >= MAX_CONSTANTS {
+      self.error_at_span(span, "Too many constants in one chunk.");
+      return 0;
+    }
+    index as u8
+  }
+
+  fn identifier_constant(&mut self, name: &Token) -> u8 {
+    self.make_constant(Constant::String(name.lexeme.clone()), name.span)
+  }
+
+  // emits a jump with a placeholder offset and returns where the offset lives.
+  fn emit_jump(&mut self, op: OpCode, line: usize) -> usize {
+    self.emit_op(op, line);
+    self.chunk().write(0xff, line);
+    self.chunk().write(0xff, line);
+    self.chunk().code.len() - 2
+  }
+
+  fn patch_jump(&mut self, offset: usize, span: Span) {
+    // -2 to skip over the offset itself.
+    let jump = self.chunk().code.len() - offset - 2;
+    if jump > u16::MAX as usize {
+      self.error_at_span(span, "Too much code to jump over.");
+    }
+    let [high, low] = (jump as u16).to_be_bytes();
+    self.chunk().code[offset] = high;
+    self.chunk().code[offset + 1] = low;
+  }
+
+  fn emit_loop(&mut self, loop_start: usize, span: Span) {
+    let line = self.line_of(span);
+    self.emit_op(OpCode::Loop, line);
+    // +2 to also jump back over the loop instruction's own offset.
+    let offset = self.chunk().code.len() - loop_start + 2;
+    if offset > u16::MAX as usize {
+      self.error_at_span(span, "Loop body too large.");
+    }
+    let [high, low] = (offset as u16).to_be_bytes();
+    self.chunk().write(high, line);
+    self.chunk().write(low, line);
+  }
+
+  // an initializer always hands back its receiver, even from a bare `return;`.
+  fn emit_return(&mut self, line: usize) {
+    if self.current().kind == FunctionKind::Initializer {
+      self.emit_op_with_operand(OpCode::GetLocal, 0, line);
+    } else {
+      self.emit_op(OpCode::Nil, line);
+    }
+    self.emit_op(OpCode::Return, line);
+  }
+
+  // scopes and variables
+
+  fn begin_scope(&mut self) {
+    self.current().scope_depth += 1;
+  }
+
+  fn end_scope(&mut self, line: usize) {
+    let function = self.current();
+    function.scope_depth -= 1;
+    let depth = function.scope_depth;
+    while self.current().locals.last().is_some_and(|local| local.depth > depth) {
+      let local = self.current().locals.pop().expect("checked above");
+      let op = if local.is_captured {
+        OpCode::CloseUpvalue
+      } else {
+        OpCode::Pop
+      };
+      self.emit_op(op, line);
+    }
+  }
+
+  // locals are added once their initializer has been compiled, so the value is already in its slot.
+  fn add_local(&mut self, name: &Token) {
+    if self.current().locals.len() >= MAX_LOCALS {
+      self.error_at_token(name, "Too many local variables in function.");
+      return;
+    }
+    let depth = self.current().scope_depth;
+    self
+      .current()
+      .locals
+      .push(Local { name: name.lexeme.to_string(), depth, is_captured: false });
+  }
+
+  // stores the value on top of the stack in a new variable.
+  fn define_variable(&mut self, name: &Token) {
+    if self.current().scope_depth > 0 {
+      self.add_local(name);
+      return;
+    }
+    let global = self.identifier_constant(name);
+    self.emit_op_with_operand(OpCode::DefineGlobal, global, name.line);
+  }
+
+  fn resolve_local(function: &FunctionState, name: &str) -> Option<u8> {
+    let slot = function.locals.iter().rposition(|local| local.name == name)?;
+    Some(slot as u8)
+  }
+
+  // finds `name` among the locals of the functions enclosing `function` (an index into
+  // `functions`), adding an upvalue to every function between the two.
+  fn resolve_upvalue(&mut self, function: usize, name: &Token) -> Option<u8> {
+    let enclosing = function.checked_sub(1)?;
+    if let Some(slot) = Compiler::resolve_local(&self.functions[enclosing], &name.lexeme) {
+      self.functions[enclosing].locals[slot as usize].is_captured = true;
+      return Some(self.add_upvalue(function, Capture::Local(slot), name));
+    }
+    let index = self.resolve_upvalue(enclosing, name)?;
+    Some(self.add_upvalue(function, Capture::Upvalue(index), name))
+  }
+
+  // closures referring to the same variable more than once capture it once.
+  fn add_upvalue(&mut self, function: usize, capture: Capture, name: &Token) -> u8 {
+    let upvalues = &mut self.functions[function].upvalues;
+    if let Some(index) = upvalues.iter().position(|existing| *existing == capture) {
+      return index as u8;
+    }
+    if upvalues.len() >= MAX_UPVALUES {
+      self.error_at_token(name, "Too many closure variables in function.");
+      return 0;
+    }
+    upvalues.push(capture);
+    (upvalues.len() - 1) as u8
+  }
+
+  // emits the get or set instruction for a variable, depending on `set`.
+  fn named_variable(&mut self, name: &Token, set: bool) {
+    let current = self.functions.len() - 1;
+    let (get_op, set_op, operand) = match Compiler::resolve_local(self.current(), &name.lexeme) {
+      Some(slot) => (OpCode::GetLocal, OpCode::SetLocal, slot),
+      None => match self.resolve_upvalue(current, name) {
+        Some(index) => (OpCode::GetUpvalue, OpCode::SetUpvalue, index),
+        None => {
+          let global = self.identifier_constant(name);
+          (OpCode::GetGlobal, OpCode::SetGlobal, global)
+        }
+      },
+    };
+    let op = if set { set_op } else { get_op };
+    self.chunk().mark_span(name.span);
+    self.emit_op_with_operand(op, operand, name.line);
+  }
+
+  fn compile_function(&mut self, declaration: &ast::FunctionStatement, kind: FunctionKind) {
+    // the loader refuses deeper files, so don't write one.
+    if self.functions.len() == MAX_FUNCTION_DEPTH {
+      self.error_at_token(&declaration.name, "Functions nested too deeply.");
+      return;
+    }
+    self.functions.push(FunctionState::new(&declaration.name.lexeme, kind));
+    self.begin_scope();
+    for param in &declaration.params {
+      self.add_local(param);
+    }
+    self.current().proto.arity = declaration.params.len();
+    for statement in &declaration.body {
+      statement.accept(self);
+    }
+    let line = self.last_line(&declaration.body).unwrap_or(declaration.name.line);
+    self.emit_return(line);
+    let mut function = self
+      .functions
+      .pop()
+      .expect("the function being compiled is always present");
+    function.proto.upvalues = function.upvalues;
+
+    let constant = self.make_constant(Constant::Function(Rc::new(function.proto)), declaration.name.span);
+    self.emit_op_with_operand(OpCode::Closure, constant, declaration.name.line);
+  }
+
+  // the line of the last byte emitted for these statements, used for the implicit return.
+  fn last_line(&mut self, statements: &[ast::Statement]) -> Option<usize> {
+    statements.last()?;
+    self.chunk().lines.last().copied()
+  }
+
+  // literals need their span for the line table, which `visit_literal` does not get.
+  fn expression(&mut self, expression: &ast::Expression) {
+    let ast::Expression::Literal(literal) = expression else {
+      return expression.accept(self);
+    };
+    let line = self.line_of(literal.span);
+    match &literal.value {
+      ast::Literal::NumberLiteral(number) => self.emit_constant(Constant::Number(*number), literal.span),
+      ast::Literal::StringLiteral(string) => self.emit_constant(Constant::String(string.clone()), literal.span),
+      ast::Literal::BooleanLiteral(true) => self.emit_op(OpCode::True, line),
+      ast::Literal::BooleanLiteral(false) => self.emit_op(OpCode::False, line),
+      ast::Literal::NullLiteral => self.emit_op(OpCode::Nil, line),
+    }
+  }
+
+  // errors
+
+  fn error_at_token(&mut self, token: &Token, message: &str) {
+    self.diagnostics.push(ErrorHandler::token_diagnostic(token, message));
+  }
+
+  fn error_at_span(&mut self, span: Span, message: &str) {
+    let location = format!("at '{}'", span.text(self.source));
+    let diagnostic = Diagnostic::error(message, self.line_of(span), &location).with_span(span);
+    self.diagnostics.push(diagnostic);
+  }
+}
+
+impl Visitor<()> for Compiler<'_> {
+  fn visit(&mut self, expression: &ast::Expression) {
+    self.expression(expression)
+  }
+
+  fn visit_program(&mut self, program: &ast::Program) {
+    for statement in &program.statements {
+      statement.accept(self);
+    }
+  }
+
+  fn visit_statement(&mut self, statement: &ast::Statement) {
+    statement.accept(self)
+  }
+
+  fn visit_print_statement(&mut self, print_statement: &ast::PrintStatement) {
+    self.expression(&print_statement.expression);
+    let line = self.line_of(print_statement.expression.span());
+    self.emit_op(OpCode::Print, line);
+  }
+
+  fn visit_expression_statement(&mut self, expression_statement: &ast::ExpressionStatement) {
+    self.expression(&expression_statement.expression);
+    let line = self.line_of(expression_statement.expression.span());
+    self.emit_op(OpCode::Pop, line);
+  }
+
+  fn visit_variable_statement(&mut self, variable_statement: &ast::VariableStatement) {
+    match &variable_statement.initializer {
+      Some(initializer) => self.expression(initializer),
+      None => self.emit_op(OpCode::Nil, variable_statement.name.line),
+    }
+    self.define_variable(&variable_statement.name);
+  }
+
+  fn visit_block_statement(&mut self, block_statement: &ast::BlockStatement) {
+    self.begin_scope();
+    for statement in &block_statement.statements {
+      statement.accept(self);
+    }
+    let line = self.chunk().lines.last().copied().unwrap_or(1);
+    self.end_scope(line);
+  }
+
+  fn visit_if_statement(&mut self, if_statement: &ast::IfStatement) {
+    let condition_span = if_statement.condition.span();
+    let line = self.line_of(condition_span);
+    self.expression(&if_statement.condition);
+    let then_jump = self.emit_jump(OpCode::JumpIfFalse, line);
+    self.emit_op(OpCode::Pop, line);
+    if_statement.then_branch.accept(self);
+    let else_jump = self.emit_jump(OpCode::Jump, line);
+    self.patch_jump(then_jump, condition_span);
+    self.emit_op(OpCode::Pop, line);
+    if let Some(else_branch) = &if_statement.else_branch {
+      else_branch.accept(self);
+    }
+    self.patch_jump(else_jump, condition_span);
+  }
+
+  fn visit_while_statement(&mut self, while_statement: &ast::WhileStatement) {
+    let condition_span = while_statement.condition.span();
+    let line = self.line_of(condition_span);
+    let loop_start = self.chunk().code.len();
+    self.expression(&while_statement.condition);
+    let exit_jump = self.emit_jump(OpCode::JumpIfFalse, line);
+    self.emit_op(OpCode::Pop, line);
+    while_statement.body.accept(self);
+    self.emit_loop(loop_start, condition_span);
+    self.patch_jump(exit_jump, condition_span);
+    self.emit_op(OpCode::Pop, line);
+  }
+
+  // a local function is declared before its body is compiled, so it can call itself.
+  fn visit_function_statement(&mut self, function_statement: &Rc<ast::FunctionStatement>) {
+    let name = &function_statement.name;
+    if self.current().scope_depth > 0 {
+      self.add_local(name);
+      self.compile_function(function_statement, FunctionKind::Function);
+      return;
+    }
+    self.compile_function(function_statement, FunctionKind::Function);
+    self.define_variable(name);
+  }
+
+  fn visit_return_statement(&mut self, return_statement: &ast::ReturnStatement) {
+    let line = return_statement.keyword.line;
+    match &return_statement.value {
+      Some(value) => {
+        self.expression(value);
+        self.emit_op(OpCode::Return, line);
+      }
+      None => self.emit_return(line),
+    }
+  }
+
+  /*
+  the class is left on the stack while its methods are attached. a subclass also
+  keeps its superclass in a local named `super`, in a scope around the methods.
+  */
+  fn visit_class_statement(&mut self, class_statement: &ast::ClassStatement) {
+    let name = &class_statement.name;
+    let name_constant = self.identifier_constant(name);
+    if self.current().scope_depth > 0 {
+      self.add_local(name);
+      self.emit_op_with_operand(OpCode::Class, name_constant, name.line);
+    } else {
+      self.emit_op_with_operand(OpCode::Class, name_constant, name.line);
+      self.emit_op_with_operand(OpCode::DefineGlobal, name_constant, name.line);
+    }
+
+    if let Some(superclass) = &class_statement.superclass {
+      self.named_variable(&superclass.name, false);
+      self.begin_scope();
+      let super_token = Token::new(
+        TokenEnum::Super,
+        Symbol::intern("super"),
+        Symbol::intern(""),
+        superclass.span,
+        superclass.name.line,
+        0,
+      );
+      self.add_local(&super_token);
+      self.named_variable(name, false);
+      self.chunk().mark_span(superclass.name.span);
+      self.emit_op(OpCode::Inherit, superclass.name.line);
+    }
+
+    self.named_variable(name, false);
+    for method in &class_statement.methods {
+      let kind = match method.name.lexeme.as_str() {
+        "init" => FunctionKind::Initializer,
+        _ => FunctionKind::Method,
+      };
+      self.compile_function(method, kind);
+      let method_constant = self.identifier_constant(&method.name);
+      self.emit_op_with_operand(OpCode::Method, method_constant, method.name.line);
+    }
+    self.emit_op(OpCode::Pop, name.line);
+
+    if class_statement.superclass.is_some() {
+      self.end_scope(name.line);
+    }
+  }
+
+  fn visit_binary_expression(&mut self, binary_expression: &ast::BinaryExpression) {
+    self.expression(&binary_expression.left);
+    self.expression(&binary_expression.right);
+    let operator = &binary_expression.operator;
+    let line = operator.line;
+    self.chunk().mark_span(operator.span);
+    match operator.kind {
+      TokenEnum::EqualEqual => self.emit_op(OpCode::Equal, line),
+      TokenEnum::BangEqual => {
+        self.emit_op(OpCode::Equal, line);
+        self.emit_op(OpCode::Not, line);
+      }
+      TokenEnum::Greater => self.emit_op(OpCode::Greater, line),
+      TokenEnum::GreaterEqual => self.emit_op(OpCode::GreaterEqual, line),
+      TokenEnum::Less => self.emit_op(OpCode::Less, line),
+      TokenEnum::LessEqual => self.emit_op(OpCode::LessEqual, line),
+      TokenEnum::Plus => self.emit_op(OpCode::Add, line),
+      TokenEnum::Minus => self.emit_op(OpCode::Subtract, line),
+      TokenEnum::Star => self.emit_op(OpCode::Multiply, line),
+      TokenEnum::Slash => self.emit_op(OpCode::Divide, line),
+      _ => unreachable!("the parser only builds binary expressions from binary operators"),
+    }
+  }
+
+  fn visit_unary_expression(&mut self, unary_expression: &ast::UnaryExpression) {
+    self.expression(&unary_expression.right);
+    let operator = &unary_expression.operator;
+    self.chunk().mark_span(operator.span);
+    match operator.kind {
+      TokenEnum::Bang => self.emit_op(OpCode::Not, operator.line),
+      TokenEnum::Minus => self.emit_op(OpCode::Negate, operator.line),
+      _ => unreachable!("the parser only builds unary expressions from '!' and '-'"),
+    }
+  }
+
+  // both operators leave the deciding operand on the stack without evaluating the other.
+  fn visit_logical_expression(&mut self, logical_expression: &ast::LogicalExpression) {
+    let operator = &logical_expression.operator;
+    let line = operator.line;
+    self.expression(&logical_expression.left);
+    if operator.kind == TokenEnum::And {
+      let end_jump = self.emit_jump(OpCode::JumpIfFalse, line);
+      self.emit_op(OpCode::Pop, line);
+      self.expression(&logical_expression.right);
+      self.patch_jump(end_jump, operator.span);
+      return;
+    }
+    let else_jump = self.emit_jump(OpCode::JumpIfFalse, line);
+    let end_jump = self.emit_jump(OpCode::Jump, line);
+    self.patch_jump(else_jump, operator.span);
+    self.emit_op(OpCode::Pop, line);
+    self.expression(&logical_expression.right);
+    self.patch_jump(end_jump, operator.span);
+  }
+
+  // `object.method(...)` becomes one `OP_INVOKE`, which skips allocating a bound method.
+  fn visit_call_expression(&mut self, call_expression: &ast::CallExpression) {
+    let method = match call_expression.callee.as_ref() {
+      ast::Expression::GetExpression(get_expression) => {
+        self.expression(&get_expression.object);
+        Some((self.identifier_constant(&get_expression.name), get_expression.name.span))
+      }
+      callee => {
+        self.expression(callee);
+        None
+      }
+    };
+    for argument in &call_expression.arguments {
+      self.expression(argument);
+    }
+    // the parser already reported calls with more arguments than an operand can hold.
+    let count = call_expression.arguments.len().min(MAX_ARGUMENTS) as u8;
+    let paren = &call_expression.paren;
+    let line = paren.line;
+    match method {
+      Some((name, name_span)) => {
+        self.chunk().mark_span(name_span.to(paren.span));
+        self.emit_op_with_operand(OpCode::Invoke, name, line);
+        self.chunk().write(count, line);
+      }
+      None => {
+        self.chunk().mark_span(paren.span);
+        self.emit_op_with_operand(OpCode::Call, count, line);
+      }
+    }
+  }
+
+  fn visit_get_expression(&mut self, get_expression: &ast::GetExpression) {
+    self.expression(&get_expression.object);
+    let name = self.identifier_constant(&get_expression.name);
+    self.chunk().mark_span(get_expression.name.span);
+    self.emit_op_with_operand(OpCode::GetProperty, name, get_expression.name.line);
+  }
+
+  fn visit_set_expression(&mut self, set_expression: &ast::SetExpression) {
+    self.expression(&set_expression.object);
+    self.expression(&set_expression.value);
+    let name = self.identifier_constant(&set_expression.name);
+    self.chunk().mark_span(set_expression.name.span);
+    self.emit_op_with_operand(OpCode::SetProperty, name, set_expression.name.line);
+  }
+
+  fn visit_this_expression(&mut self, this_expression: &ast::ThisExpression) {
+    self.named_variable(&this_expression.keyword, false);
+  }
+
+  // looks the method up on the superclass and binds it to `this`.
+  fn visit_super_expression(&mut self, super_expression: &ast::SuperExpression) {
+    let keyword = &super_expression.keyword;
+    let this = Token::new(
+      TokenEnum::This,
+      Symbol::intern("this"),
+      Symbol::intern(""),
+      keyword.span,
+      keyword.line,
+      keyword.column,
+    );
+    self.named_variable(&this, false);
+    self.named_variable(keyword, false);
+    let name = self.identifier_constant(&super_expression.method);
+    self.chunk().mark_span(super_expression.method.span);
+    self.emit_op_with_operand(OpCode::GetSuper, name, super_expression.method.line);
+  }
+
+  fn visit_grouping_expression(&mut self, grouping_expression: &ast::GroupingExpression) {
+    self.expression(&grouping_expression.expression);
+  }
+
+  fn visit_variable_expression(&mut self, variable_expression: &ast::VariableExpression) {
+    self.named_variable(&variable_expression.name, false);
+  }
+
+  fn visit_assignment_expression(&mut self, assignment_expression: &ast::AssignmentExpression) {
+    self.expression(&assignment_expression.value);
+    self.named_variable(&assignment_expression.name, true);
+  }
+
+  fn visit_literal(&mut self, _literal: &ast::Literal) {
+    unreachable!("literals are compiled by `Compiler::expression`, which knows their span")
+  }
+
+  fn visit_expression(&mut self, expression: &ast::Expression) {
+    self.expression(expression)
+  }
+}
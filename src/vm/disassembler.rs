@@ -0,0 +1,86 @@
+use super::chunk::{Chunk, Constant, FunctionProto, OpCode, Operand};
+
+/*
+prints a function and every function nested in its constants, in the format clox uses:
+
+== <fn add> ==
+0000    1 OP_GET_LOCAL        1
+0002    | OP_GET_LOCAL        2
+0004    | OP_ADD
+0005    | OP_RETURN
+
+the second column is the source line, or `|` when it repeats the previous instruction's.
+an `OP_CLOSURE` is followed by one line for each variable the new closure captures.
+*/
+pub fn disassemble(function: &FunctionProto) -> String {
+  let mut output = String::new();
+  disassemble_function(function, &mut output);
+  output
+}
+
+fn disassemble_function(function: &FunctionProto, output: &mut String) {
+  output.push_str(&format!("== {} ==\n", function));
+  let chunk = &function.chunk;
+  let mut offset = 0;
+  while offset < chunk.code.len() {
+    let (text, next) = disassemble_instruction(chunk, offset);
+    output.push_str(&text);
+    output.push('\n');
+    offset = next;
+  }
+  for constant in &chunk.constants {
+    if let Constant::Function(nested) = constant {
+      output.push('\n');
+      disassemble_function(nested, output);
+    }
+  }
+}
+
+// one line for the instruction at `offset`, and the offset of the next one.
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
+  let line = match offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
+    true => String::from("   |"),
+    false => format!("{:4}", chunk.lines[offset]),
+  };
+  let prefix = format!("{:04} {} ", offset, line);
+  let byte = chunk.code[offset];
+  let Some(op) = OpCode::from_byte(byte) else {
+    return (format!("{}unknown opcode {}", prefix, byte), offset + 1);
+  };
+  if offset + op.size() > chunk.code.len() {
+    return (format!("{}{:<16} <truncated>", prefix, op.name()), chunk.code.len());
+  }
+  let operand = match op.operand() {
+    Operand::None => return (format!("{}{}", prefix, op.name()), offset + 1),
+    Operand::Constant | Operand::Name => constant_operand(chunk, chunk.code[offset + 1] as usize),
+    Operand::Invoke => {
+      let name = constant_operand(chunk, chunk.code[offset + 1] as usize);
+      format!("{} ({} args)", name, chunk.code[offset + 2])
+    }
+    Operand::Slot | Operand::Upvalue | Operand::ArgumentCount => format!(" {:4}", chunk.code[offset + 1]),
+    Operand::Jump => {
+      let jump = chunk.read_u16(offset + 1) as usize;
+      format!(" {:04} -> {:04}", offset, offset + 3 + jump)
+    }
+    Operand::Loop => {
+      let jump = chunk.read_u16(offset + 1) as usize;
+      format!(" {:04} -> {:04}", offset, (offset + 3) as isize - jump as isize)
+    }
+  };
+  let mut text = format!("{}{:<16}{}", prefix, op.name(), operand);
+  if let (OpCode::Closure, Some(Constant::Function(function))) =
+    (op, chunk.constants.get(chunk.code[offset + 1] as usize))
+  {
+    for capture in &function.upvalues {
+      text.push_str(&format!("\n{:04}    | {:<16} {}", offset, "", capture));
+    }
+  }
+  (text, offset + op.size())
+}
+
+fn constant_operand(chunk: &Chunk, index: usize) -> String {
+  match chunk.constants.get(index) {
+    Some(constant) => format!(" {:4} {}", index, constant),
+    None => format!(" {:4} <missing constant>", index),
+  }
+}
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+
+use super::object::{BoundMethod, Class, Closure, Instance, NativeFunction, Object, Upvalue};
+use super::shape::ShapeId;
+use super::value::{Value, ValueKind};
+
+/*
+the vm's managed heap. objects live in a table of slots and values refer to them by
+index (`ObjRef`); freed slots are reused by later allocations.
+
+memory is reclaimed by a tracing mark-and-sweep collector. the heap can't see the vm's
+roots, so the vm asks `should_collect` before every allocation and, when it says so,
+calls `collect` with everything it holds directly: the value stack, the globals, the
+closures of the active call frames and the open upvalues. anything not reachable from
+those is freed. the root shapes of the classes that were freed are handed back, so
+the vm can release the shapes and forget anything cached about them.
+
+after a collection the next one is scheduled once the live size has grown by
+`growth_factor`. with `stress` set, every allocation collects first, which flushes out
+objects the vm forgot to root.
+*/
+
+pub const GC_GROWTH_FACTOR: f64 = 2.0;
+// collecting a nearly empty heap is all overhead, so the first collection waits for this many bytes.
+const GC_MINIMUM_THRESHOLD: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(u32);
+
+// the raw index, for value representations that pack it into their own bits.
+#[cfg(feature = "nan-boxing")]
+impl ObjRef {
+  pub(super) fn from_index(index: u32) -> Self {
+    ObjRef(index)
+  }
+
+  pub(super) fn index(self) -> u32 {
+    self.0
+  }
+}
+
+struct Slot {
+  marked: bool,
+  size: usize, // as estimated at allocation
+  object: Object,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GcStats {
+  pub collections: usize,
+  pub objects_freed: usize,
+}
+
+pub struct Heap {
+  pub growth_factor: f64,
+  pub stress: bool,
+  pub stats: GcStats,
+  slots: Vec<Option<Slot>>,
+  free: Vec<u32>,
+  strings: HashMap<Box<str>, ObjRef>, // intern table; weak, entries go away with their string
+  bytes_allocated: usize,
+  next_gc: usize,
+  gray: Vec<ObjRef>,
+}
+
+impl Heap {
+  pub fn new() -> Self {
+    Heap {
+      growth_factor: GC_GROWTH_FACTOR,
+      stress: false,
+      stats: GcStats::default(),
+      slots: vec![],
+      free: vec![],
+      strings: HashMap::new(),
+      bytes_allocated: 0,
+      next_gc: GC_MINIMUM_THRESHOLD,
+      gray: vec![],
+    }
+  }
+
+  pub fn should_collect(&self) -> bool {
+    self.stress || self.bytes_allocated > self.next_gc
+  }
+
+  pub fn allocate(&mut self, object: Object) -> ObjRef {
+    let size = object.size();
+    self.bytes_allocated += size;
+    let slot = Some(Slot { marked: false, size, object });
+    match self.free.pop() {
+      Some(index) => {
+        self.slots[index as usize] = slot;
+        ObjRef(index)
+      }
+      None => {
+        self.slots.push(slot);
+        ObjRef((self.slots.len() - 1) as u32)
+      }
+    }
+  }
+
+  // the interned copy of `string`, if one is alive.
+  pub fn find_string(&self, string: &str) -> Option<ObjRef> {
+    self.strings.get(string).copied()
+  }
+
+  // callers check `find_string` first, so equal strings always share one object.
+  pub fn allocate_string(&mut self, string: &str) -> ObjRef {
+    let object = self.allocate(Object::String(Box::from(string)));
+    self.strings.insert(Box::from(string), object);
+    object
+  }
+
+  pub fn live_objects(&self) -> usize {
+    self.slots.len() - self.free.len()
+  }
+
+  // access
+
+  pub fn get(&self, object: ObjRef) -> &Object {
+    match &self.slots[object.0 as usize] {
+      Some(slot) => &slot.object,
+      None => panic!("use of a collected object {:?}", object),
+    }
+  }
+
+  fn get_mut(&mut self, object: ObjRef) -> &mut Object {
+    match &mut self.slots[object.0 as usize] {
+      Some(slot) => &mut slot.object,
+      None => panic!("use of a collected object {:?}", object),
+    }
+  }
+
+  pub fn string(&self, object: ObjRef) -> &str {
+    match self.get(object) {
+      Object::String(string) => string,
+      _ => unreachable!("expected a string"),
+    }
+  }
+
+  pub fn closure(&self, object: ObjRef) -> &Closure {
+    match self.get(object) {
+      Object::Closure(closure) => closure,
+      _ => unreachable!("expected a closure"),
+    }
+  }
+
+  pub fn closure_mut(&mut self, object: ObjRef) -> &mut Closure {
+    match self.get_mut(object) {
+      Object::Closure(closure) => closure,
+      _ => unreachable!("expected a closure"),
+    }
+  }
+
+  pub fn upvalue(&self, object: ObjRef) -> &Upvalue {
+    match self.get(object) {
+      Object::Upvalue(upvalue) => upvalue,
+      _ => unreachable!("expected an upvalue"),
+    }
+  }
+
+  pub fn upvalue_mut(&mut self, object: ObjRef) -> &mut Upvalue {
+    match self.get_mut(object) {
+      Object::Upvalue(upvalue) => upvalue,
+      _ => unreachable!("expected an upvalue"),
+    }
+  }
+
+  pub fn native_function(&self, object: ObjRef) -> &NativeFunction {
+    match self.get(object) {
+      Object::NativeFunction(native) => native,
+      _ => unreachable!("expected a native function"),
+    }
+  }
+
+  pub fn class(&self, object: ObjRef) -> &Class {
+    match self.get(object) {
+      Object::Class(class) => class,
+      _ => unreachable!("expected a class"),
+    }
+  }
+
+  pub fn class_mut(&mut self, object: ObjRef) -> &mut Class {
+    match self.get_mut(object) {
+      Object::Class(class) => class,
+      _ => unreachable!("expected a class"),
+    }
+  }
+
+  pub fn instance(&self, object: ObjRef) -> &Instance {
+    match self.get(object) {
+      Object::Instance(instance) => instance,
+      _ => unreachable!("expected an instance"),
+    }
+  }
+
+  pub fn instance_mut(&mut self, object: ObjRef) -> &mut Instance {
+    match self.get_mut(object) {
+      Object::Instance(instance) => instance,
+      _ => unreachable!("expected an instance"),
+    }
+  }
+
+  pub fn bound_method(&self, object: ObjRef) -> &BoundMethod {
+    match self.get(object) {
+      Object::BoundMethod(bound_method) => bound_method,
+      _ => unreachable!("expected a bound method"),
+    }
+  }
+
+  // prints exactly like `values::Value`, so both backends produce the same output.
+  pub fn format(&self, value: &Value) -> String {
+    match value.kind() {
+      ValueKind::Nil => String::from("nil"),
+      ValueKind::Boolean(boolean) => boolean.to_string(),
+      ValueKind::Number(number) => crate::values::Value::Number(number).to_string(),
+      ValueKind::String(string) => self.string(string).to_owned(),
+      ValueKind::Closure(closure) => self.closure(closure).to_string(),
+      ValueKind::NativeFunction(_) => String::from("<native fn>"),
+      ValueKind::Class(class) => self.class(class).name.to_string(),
+      ValueKind::Instance(instance) => format!("{} instance", self.class(self.instance(instance).class).name),
+      ValueKind::BoundMethod(bound_method) => self.closure(self.bound_method(bound_method).method).to_string(),
+    }
+  }
+
+  // collection
+
+  // returns the root shapes of the classes it freed.
+  pub fn collect(&mut self, roots: impl IntoIterator<Item = ObjRef>) -> Vec<ShapeId> {
+    for root in roots {
+      self.mark(root);
+    }
+    self.trace_references();
+    let freed_shapes = self.sweep();
+    self.next_gc = GC_MINIMUM_THRESHOLD.max((self.bytes_allocated as f64 * self.growth_factor) as usize);
+    self.stats.collections += 1;
+    freed_shapes
+  }
+
+  fn mark(&mut self, object: ObjRef) {
+    let slot = self.slots[object.0 as usize]
+      .as_mut()
+      .expect("a live object refers to a collected one");
+    if !slot.marked {
+      slot.marked = true;
+      self.gray.push(object);
+    }
+  }
+
+  fn trace_references(&mut self) {
+    let mut references = vec![];
+    while let Some(object) = self.gray.pop() {
+      self.get(object).references(&mut references);
+      for reference in references.drain(..) {
+        self.mark(reference);
+      }
+    }
+  }
+
+  fn sweep(&mut self) -> Vec<ShapeId> {
+    let mut freed_shapes = vec![];
+    for index in 0..self.slots.len() {
+      let Some(slot) = &mut self.slots[index] else {
+        continue;
+      };
+      if slot.marked {
+        slot.marked = false;
+        continue;
+      }
+      let slot = self.slots[index].take().expect("checked above");
+      self.bytes_allocated -= slot.size;
+      match &slot.object {
+        Object::String(string) => {
+          self.strings.remove(string);
+        }
+        Object::Class(class) => freed_shapes.push(class.shape),
+        _ => {}
+      }
+      self.free.push(index as u32);
+      self.stats.objects_freed += 1;
+    }
+    freed_shapes
+  }
+}
@@ -0,0 +1,156 @@
+use std::rc::Rc;
+
+use super::chunk::{FunctionProto, OpCode};
+use super::heap::ObjRef;
+use super::shape::ShapeId;
+
+/*
+inline caches for `OP_GET_PROPERTY`, `OP_SET_PROPERTY` and `OP_INVOKE`. what a property
+name resolves to depends only on the receiver's shape, so each of those instructions
+remembers the answers for the shapes it has seen:
+
+  empty        never executed, or reset
+  monomorphic  one shape, the common case
+  polymorphic  up to POLYMORPHIC_LIMIT shapes, checked in order
+  megamorphic  too many shapes; the instruction stops caching and always looks up
+
+the caches of a function are shared by all of its closures and found by instruction
+offset, so the bytecode itself doesn't change. method answers go stale when a class
+gains methods, so the vm bumps an epoch on `OP_METHOD` and `OP_INHERIT`, and a
+function's caches are reset the next time they are used under a newer epoch.
+
+caches are not traced by the collector. a cached method is only returned for an
+instance of its shape, and that instance keeps the class, and so the method, alive.
+once a class is freed its shapes are reused for other classes, so the vm bumps the
+epoch after any collection that frees a class too.
+*/
+
+const POLYMORPHIC_LIMIT: usize = 4;
+const NO_CACHE: u32 = u32::MAX;
+
+// what a property name resolved to for one shape.
+#[derive(Debug, Clone, Copy)]
+pub enum CacheTarget {
+  Field(usize),      // the index of an existing field
+  NewField(ShapeId), // set only: the field doesn't exist yet; add it last and move to this shape
+  Method(ObjRef),    // a closure on the class, when no field has the name
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CacheEntry {
+  shape: ShapeId,
+  target: CacheTarget,
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum InlineCache {
+  #[default]
+  Empty,
+  Monomorphic(CacheEntry),
+  Polymorphic(Vec<CacheEntry>),
+  Megamorphic,
+}
+
+impl InlineCache {
+  pub fn lookup(&self, shape: ShapeId) -> Option<CacheTarget> {
+    let entry = match self {
+      InlineCache::Monomorphic(entry) => Some(entry).filter(|entry| entry.shape == shape),
+      InlineCache::Polymorphic(entries) => entries.iter().find(|entry| entry.shape == shape),
+      InlineCache::Empty | InlineCache::Megamorphic => None,
+    };
+    entry.map(|entry| entry.target)
+  }
+
+  pub fn insert(&mut self, shape: ShapeId, target: CacheTarget) {
+    let entry = CacheEntry { shape, target };
+    *self = match std::mem::take(self) {
+      InlineCache::Empty => InlineCache::Monomorphic(entry),
+      InlineCache::Monomorphic(first) => InlineCache::Polymorphic(vec![first, entry]),
+      InlineCache::Polymorphic(mut entries) if entries.len() < POLYMORPHIC_LIMIT => {
+        entries.push(entry);
+        InlineCache::Polymorphic(entries)
+      }
+      InlineCache::Polymorphic(_) | InlineCache::Megamorphic => InlineCache::Megamorphic,
+    };
+  }
+}
+
+pub struct FunctionCaches {
+  function: Rc<FunctionProto>, // kept alive, so no other function can take its address
+  epoch: u32,
+  slots: Vec<u32>, // instruction offset → index in `caches`, NO_CACHE for everything else
+  caches: Vec<InlineCache>,
+}
+
+impl FunctionCaches {
+  pub fn new(function: Rc<FunctionProto>, epoch: u32) -> Self {
+    let code = &function.chunk.code;
+    let mut slots = vec![NO_CACHE; code.len()];
+    let mut count = 0;
+    let mut offset = 0;
+    while offset < code.len() {
+      let Some(op) = OpCode::from_byte(code[offset]) else {
+        break; // only verified bytecode runs, so this is never reached
+      };
+      if matches!(op, OpCode::GetProperty | OpCode::SetProperty | OpCode::Invoke) {
+        slots[offset] = count;
+        count += 1;
+      }
+      offset += op.size();
+    }
+    let caches = vec![InlineCache::Empty; count as usize];
+    FunctionCaches { function, epoch, slots, caches }
+  }
+
+  pub fn belongs_to(&self, function: &Rc<FunctionProto>) -> bool {
+    Rc::ptr_eq(&self.function, function)
+  }
+
+  // the cache of the instruction at `offset`, emptied first if methods changed since it was filled.
+  pub fn at(&mut self, offset: usize, epoch: u32) -> &mut InlineCache {
+    if self.epoch != epoch {
+      self.caches.fill(InlineCache::Empty);
+      self.epoch = epoch;
+    }
+    &mut self.caches[self.slots[offset] as usize]
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheCounters {
+  pub hits: usize,
+  pub misses: usize,
+}
+
+impl CacheCounters {
+  // none when the instruction never ran.
+  pub fn hit_rate(&self) -> Option<f64> {
+    let total = self.hits + self.misses;
+    match total {
+      0 => None,
+      _ => Some(self.hits as f64 / total as f64),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+  pub get_property: CacheCounters,
+  pub set_property: CacheCounters,
+  pub invoke: CacheCounters,
+}
+
+impl CacheStats {
+  pub fn record(&mut self, op: OpCode, hit: bool) {
+    let counters = match op {
+      OpCode::GetProperty => &mut self.get_property,
+      OpCode::SetProperty => &mut self.set_property,
+      OpCode::Invoke => &mut self.invoke,
+      _ => unreachable!("{} has no inline cache", op.name()),
+    };
+    match hit {
+      true => counters.hits += 1,
+      false => counters.misses += 1,
+    }
+  }
+}
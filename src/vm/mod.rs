@@ -0,0 +1,22 @@
+mod assembler;
+mod bytecode_file;
+mod chunk;
+mod compiler;
+mod disassembler;
+mod heap;
+mod inline_cache;
+#[cfg(feature = "nan-boxing")]
+mod nan_boxing;
+mod object;
+mod shape;
+mod value;
+mod verifier;
+#[allow(clippy::module_inception)]
+mod vm;
+pub use assembler::*;
+pub use bytecode_file::*;
+pub use chunk::*;
+pub use compiler::*;
+pub use disassembler::*;
+pub use verifier::*;
+pub use vm::*;
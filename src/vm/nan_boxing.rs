@@ -0,0 +1,104 @@
+use std::fmt::{Debug, Formatter};
+
+use super::heap::ObjRef;
+use super::value::ValueKind;
+
+/*
+a vm value packed into the 64 bits of an f64, enabled by the `nan-boxing` feature.
+
+every number is stored as itself. the other values hide in quiet NaNs, which arithmetic
+never produces with these exact bits (a real NaN is stored as the canonical `f64::NAN`):
+
+  number     any f64 whose bits don't contain QUIET_NAN
+  nil        QUIET_NAN | 1
+  false      QUIET_NAN | 2
+  true       QUIET_NAN | 3
+  object     SIGN_BIT | QUIET_NAN | kind << 32 | heap index
+
+the object kind keeps `is_string` and friends from needing the heap.
+*/
+
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+const QUIET_NAN: u64 = 0x7ffc_0000_0000_0000;
+
+const NIL: u64 = QUIET_NAN | 1;
+const FALSE: u64 = QUIET_NAN | 2;
+const TRUE: u64 = QUIET_NAN | 3;
+
+const OBJECT: u64 = SIGN_BIT | QUIET_NAN;
+const KIND_SHIFT: u32 = 32;
+const KIND_MASK: u64 = 0b111;
+
+const STRING: u64 = 0;
+const CLOSURE: u64 = 1;
+const NATIVE_FUNCTION: u64 = 2;
+const CLASS: u64 = 3;
+const INSTANCE: u64 = 4;
+const BOUND_METHOD: u64 = 5;
+
+#[derive(Clone, Copy)]
+pub struct Value(u64);
+
+// the point of the feature.
+const _: () = assert!(std::mem::size_of::<Value>() == 8);
+
+impl Value {
+  pub fn new(kind: ValueKind) -> Self {
+    let bits = match kind {
+      ValueKind::Nil => NIL,
+      ValueKind::Boolean(false) => FALSE,
+      ValueKind::Boolean(true) => TRUE,
+      ValueKind::Number(number) if number.is_nan() => f64::NAN.to_bits(),
+      ValueKind::Number(number) => number.to_bits(),
+      ValueKind::String(object) => pack(STRING, object),
+      ValueKind::Closure(object) => pack(CLOSURE, object),
+      ValueKind::NativeFunction(object) => pack(NATIVE_FUNCTION, object),
+      ValueKind::Class(object) => pack(CLASS, object),
+      ValueKind::Instance(object) => pack(INSTANCE, object),
+      ValueKind::BoundMethod(object) => pack(BOUND_METHOD, object),
+    };
+    Value(bits)
+  }
+
+  pub fn kind(&self) -> ValueKind {
+    if self.0 & QUIET_NAN != QUIET_NAN {
+      return ValueKind::Number(f64::from_bits(self.0));
+    }
+    match self.0 {
+      NIL => ValueKind::Nil,
+      FALSE => ValueKind::Boolean(false),
+      TRUE => ValueKind::Boolean(true),
+      bits => {
+        let object = ObjRef::from_index(bits as u32);
+        match (bits >> KIND_SHIFT) & KIND_MASK {
+          STRING => ValueKind::String(object),
+          CLOSURE => ValueKind::Closure(object),
+          NATIVE_FUNCTION => ValueKind::NativeFunction(object),
+          CLASS => ValueKind::Class(object),
+          INSTANCE => ValueKind::Instance(object),
+          BOUND_METHOD => ValueKind::BoundMethod(object),
+          _ => unreachable!("invalid nan-boxed value {:#x}", bits),
+        }
+      }
+    }
+  }
+
+  // numbers compare as numbers (so NaN != NaN and 0 == -0); everything else, strings
+  // included since they are interned, is equal only to the same bits.
+  pub fn equals(&self, other: &Value) -> bool {
+    match (self.kind(), other.kind()) {
+      (ValueKind::Number(left), ValueKind::Number(right)) => left == right,
+      _ => self.0 == other.0,
+    }
+  }
+}
+
+fn pack(kind: u64, object: ObjRef) -> u64 {
+  OBJECT | kind << KIND_SHIFT | object.index() as u64
+}
+
+impl Debug for Value {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:?}", self.kind())
+  }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use super::chunk::FunctionProto;
+use super::heap::ObjRef;
+use super::shape::ShapeId;
+use super::value::Value;
+use crate::shared::symbol::Symbol;
+
+// everything the vm allocates on its heap; values refer to these through an `ObjRef`.
+pub enum Object {
+  String(Box<str>),
+  Closure(Closure),
+  Upvalue(Upvalue),
+  NativeFunction(NativeFunction),
+  Class(Class),
+  Instance(Instance),
+  BoundMethod(BoundMethod),
+}
+
+impl Object {
+  // every heap object this one keeps alive.
+  pub fn references(&self, found: &mut Vec<ObjRef>) {
+    match self {
+      Object::String(_) | Object::NativeFunction(_) => {}
+      Object::Closure(closure) => found.extend(&closure.upvalues),
+      Object::Upvalue(Upvalue::Open(_)) => {}
+      Object::Upvalue(Upvalue::Closed(value)) => found.extend(value.as_object()),
+      Object::Class(class) => found.extend(class.methods.values()),
+      Object::Instance(instance) => {
+        found.push(instance.class);
+        found.extend(instance.fields.iter().filter_map(Value::as_object));
+      }
+      Object::BoundMethod(bound_method) => {
+        found.extend(bound_method.receiver.as_object());
+        found.push(bound_method.method);
+      }
+    }
+  }
+
+  // an estimate of the memory behind the object, used to decide when to collect.
+  pub fn size(&self) -> usize {
+    let payload = match self {
+      Object::String(string) => string.len(),
+      Object::Closure(closure) => closure.function.upvalues.len() * std::mem::size_of::<ObjRef>(),
+      Object::Upvalue(_) | Object::NativeFunction(_) | Object::BoundMethod(_) => 0,
+      Object::Class(class) => class.name.len() + class.methods.len() * std::mem::size_of::<(Symbol, ObjRef)>(),
+      Object::Instance(instance) => instance.fields.len() * std::mem::size_of::<Value>(),
+    };
+    std::mem::size_of::<Object>() + payload
+  }
+}
+
+// a function value created at runtime from a compiled prototype.
+// prototypes are immutable compiler output shared with the constant pool, so they stay reference counted.
+pub struct Closure {
+  pub function: Rc<FunctionProto>,
+  pub upvalues: Vec<ObjRef>, // one per capture in `function.upvalues`, filled in by `OP_CLOSURE`
+  pub caches: usize,         // where the vm keeps the inline caches of `function`
+}
+
+impl Closure {
+  pub fn new(function: Rc<FunctionProto>, caches: usize) -> Self {
+    let upvalues = Vec::with_capacity(function.upvalues.len());
+    Closure { function, upvalues, caches }
+  }
+}
+
+impl Display for Closure {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.function)
+  }
+}
+
+// a variable captured by a closure. closures that capture the same variable share one upvalue,
+// which points at the variable's stack slot until the slot goes away and then holds the value itself.
+pub enum Upvalue {
+  Open(usize), // an index into the vm stack
+  Closed(Value),
+}
+
+pub struct NativeFunction {
+  pub arity: usize,
+  pub function: fn(&[Value]) -> Value,
+}
+
+impl NativeFunction {
+  pub fn new(arity: usize, function: fn(&[Value]) -> Value) -> Self {
+    NativeFunction { arity, function }
+  }
+}
+
+// seconds since the unix epoch, used by benchmarks to time themselves.
+pub fn clock(_arguments: &[Value]) -> Value {
+  let elapsed = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .unwrap_or_default();
+  Value::number(elapsed.as_secs_f64())
+}
+
+// inherited methods are copied into the subclass when it is created, so lookups never walk a chain.
+pub struct Class {
+  pub name: Symbol,
+  pub methods: HashMap<Symbol, ObjRef>, // closures
+  pub shape: ShapeId,                   // the shape of a new instance, before it has any fields
+}
+
+impl Class {
+  pub fn new(name: Symbol, shape: ShapeId) -> Self {
+    Class { name, methods: HashMap::new(), shape }
+  }
+}
+
+// fields live in the order they were first assigned; the shape says which index holds which name.
+pub struct Instance {
+  pub class: ObjRef,
+  pub shape: ShapeId,
+  pub fields: Vec<Value>,
+}
+
+impl Instance {
+  pub fn new(class: ObjRef, shape: ShapeId) -> Self {
+    Instance { class, shape, fields: vec![] }
+  }
+}
+
+// a method read off an instance, remembering the instance to use as `this`.
+pub struct BoundMethod {
+  pub receiver: Value,
+  pub method: ObjRef, // a closure
+}
+
+impl BoundMethod {
+  pub fn new(receiver: Value, method: ObjRef) -> Self {
+    BoundMethod { receiver, method }
+  }
+}
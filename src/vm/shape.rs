@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use crate::shared::symbol::Symbol;
+
+/*
+hidden classes for instances. a shape records which fields an instance has and where
+each one lives in its field vector; instances that gained the same fields in the same
+order share a shape, so "where is `x`" is answered once per shape instead of once per
+access (see `inline_cache.rs`).
+
+every class starts its instances on a root shape of its own, so a shape also tells
+which class its instances belong to. adding a field moves an instance along a
+transition to the next shape, created the first time any instance takes it.
+
+the shapes reachable from a root all belong to its class and its instances, so once
+the collector frees the class the whole tree is released and its ids are reused.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShapeId(u32);
+
+struct Shape {
+  fields: HashMap<Symbol, usize>,        // field name → index in the instance's fields
+  transitions: HashMap<Symbol, ShapeId>, // the shape an instance moves to when it gains that field
+}
+
+pub struct Shapes {
+  shapes: Vec<Shape>,
+  free: Vec<ShapeId>, // released ids, handed out again before the table grows
+}
+
+impl Shapes {
+  pub fn new() -> Self {
+    Shapes { shapes: vec![], free: vec![] }
+  }
+
+  // an empty shape shared with no other class.
+  pub fn new_root(&mut self) -> ShapeId {
+    self.push(HashMap::new())
+  }
+
+  pub fn field(&self, shape: ShapeId, name: &Symbol) -> Option<usize> {
+    self.shapes[shape.0 as usize].fields.get(name).copied()
+  }
+
+  // the shape after adding `name`, which `shape` must not have yet; the new field goes last.
+  pub fn with_field(&mut self, shape: ShapeId, name: &Symbol) -> ShapeId {
+    if let Some(next) = self.shapes[shape.0 as usize].transitions.get(name) {
+      return *next;
+    }
+    let mut fields = self.shapes[shape.0 as usize].fields.clone();
+    fields.insert(name.clone(), fields.len());
+    let next = self.push(fields);
+    self.shapes[shape.0 as usize].transitions.insert(name.clone(), next);
+    next
+  }
+
+  // frees `root` and every shape reachable from it; nothing may use them any more.
+  pub fn release(&mut self, root: ShapeId) {
+    let mut pending = vec![root];
+    while let Some(shape) = pending.pop() {
+      let released = &mut self.shapes[shape.0 as usize];
+      pending.extend(released.transitions.values());
+      *released = Shape { fields: HashMap::new(), transitions: HashMap::new() };
+      self.free.push(shape);
+    }
+  }
+
+  // the shapes in use.
+  pub fn len(&self) -> usize {
+    self.shapes.len() - self.free.len()
+  }
+
+  fn push(&mut self, fields: HashMap<Symbol, usize>) -> ShapeId {
+    let shape = Shape { fields, transitions: HashMap::new() };
+    if let Some(id) = self.free.pop() {
+      self.shapes[id.0 as usize] = shape;
+      return id;
+    }
+    self.shapes.push(shape);
+    ShapeId((self.shapes.len() - 1) as u32)
+  }
+}
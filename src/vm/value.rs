@@ -0,0 +1,163 @@
+use super::heap::{Heap, ObjRef};
+use crate::values::Truthiness;
+
+// what a vm value holds; the same data model as `values::Value`, with objects living on the vm heap.
+// strings are interned, so two strings are equal exactly when they are the same object.
+#[derive(Debug, Clone, Copy)]
+pub enum ValueKind {
+  Nil,
+  Boolean(bool),
+  Number(f64),
+  String(ObjRef),
+  Closure(ObjRef),
+  NativeFunction(ObjRef),
+  Class(ObjRef),
+  Instance(ObjRef),
+  BoundMethod(ObjRef),
+}
+
+/*
+a value on the vm stack. how it is stored depends on the `nan-boxing` feature:
+
+  default      a tagged enum (`ValueKind` itself), 16 bytes
+  nan-boxing   a single `u64`, see `nan_boxing.rs`
+
+both provide `new`, `kind` and `equals`; everything else, the predicates and getters included, is
+written once, below, on top of those.
+*/
+#[cfg(not(feature = "nan-boxing"))]
+#[derive(Debug, Clone, Copy)]
+pub struct Value(ValueKind);
+
+#[cfg(not(feature = "nan-boxing"))]
+impl Value {
+  pub fn new(kind: ValueKind) -> Self {
+    Value(kind)
+  }
+
+  pub fn kind(&self) -> ValueKind {
+    self.0
+  }
+
+  // same rules as `values::Value::equals`: objects are equal only to themselves.
+  pub fn equals(&self, other: &Value) -> bool {
+    match (self.0, other.0) {
+      (ValueKind::Nil, ValueKind::Nil) => true,
+      (ValueKind::Boolean(left), ValueKind::Boolean(right)) => left == right,
+      (ValueKind::Number(left), ValueKind::Number(right)) => left == right,
+      (ValueKind::String(left), ValueKind::String(right))
+      | (ValueKind::Closure(left), ValueKind::Closure(right))
+      | (ValueKind::NativeFunction(left), ValueKind::NativeFunction(right))
+      | (ValueKind::Class(left), ValueKind::Class(right))
+      | (ValueKind::Instance(left), ValueKind::Instance(right))
+      | (ValueKind::BoundMethod(left), ValueKind::BoundMethod(right)) => left == right,
+      _ => false,
+    }
+  }
+}
+
+#[cfg(feature = "nan-boxing")]
+pub use super::nan_boxing::Value;
+
+impl Value {
+  // constructors
+  pub fn nil() -> Self {
+    Value::new(ValueKind::Nil)
+  }
+  pub fn boolean(boolean: bool) -> Self {
+    Value::new(ValueKind::Boolean(boolean))
+  }
+  pub fn number(number: f64) -> Self {
+    Value::new(ValueKind::Number(number))
+  }
+  pub fn string(string: ObjRef) -> Self {
+    Value::new(ValueKind::String(string))
+  }
+  pub fn closure(closure: ObjRef) -> Self {
+    Value::new(ValueKind::Closure(closure))
+  }
+  pub fn native_function(native: ObjRef) -> Self {
+    Value::new(ValueKind::NativeFunction(native))
+  }
+  pub fn class(class: ObjRef) -> Self {
+    Value::new(ValueKind::Class(class))
+  }
+  pub fn instance(instance: ObjRef) -> Self {
+    Value::new(ValueKind::Instance(instance))
+  }
+  pub fn bound_method(bound_method: ObjRef) -> Self {
+    Value::new(ValueKind::BoundMethod(bound_method))
+  }
+
+  #[allow(dead_code)]
+  pub fn is_nil(&self) -> bool {
+    matches!(self.kind(), ValueKind::Nil)
+  }
+  #[allow(dead_code)]
+  pub fn is_boolean(&self) -> bool {
+    matches!(self.kind(), ValueKind::Boolean(_))
+  }
+  pub fn is_number(&self) -> bool {
+    matches!(self.kind(), ValueKind::Number(_))
+  }
+  #[allow(dead_code)]
+  pub fn is_string(&self) -> bool {
+    matches!(self.kind(), ValueKind::String(_))
+  }
+
+  pub fn is_truthy(&self) -> bool {
+    match self.kind() {
+      ValueKind::Nil => false,
+      ValueKind::Boolean(boolean) => boolean,
+      _ => true,
+    }
+  }
+
+  // the heap is only consulted for strings under loose truthiness.
+  pub fn is_truthy_with(&self, truthiness: Truthiness, heap: &Heap) -> bool {
+    match (self.kind(), truthiness) {
+      (ValueKind::Number(number), Truthiness::Loose) => number != 0.0,
+      (ValueKind::String(string), Truthiness::Loose) => !heap.string(string).is_empty(),
+      _ => self.is_truthy(),
+    }
+  }
+
+  #[allow(dead_code)]
+  pub fn type_name(&self) -> &'static str {
+    match self.kind() {
+      ValueKind::Nil => "nil",
+      ValueKind::Boolean(_) => "boolean",
+      ValueKind::Number(_) => "number",
+      ValueKind::String(_) => "string",
+      ValueKind::Closure(_) | ValueKind::NativeFunction(_) | ValueKind::BoundMethod(_) => "function",
+      ValueKind::Class(_) => "class",
+      ValueKind::Instance(_) => "instance",
+    }
+  }
+
+  // getters
+  pub fn as_number(&self) -> f64 {
+    match self.kind() {
+      ValueKind::Number(number) => number,
+      _ => unreachable!(),
+    }
+  }
+  #[allow(dead_code)]
+  pub fn as_boolean(&self) -> bool {
+    match self.kind() {
+      ValueKind::Boolean(boolean) => boolean,
+      _ => unreachable!(),
+    }
+  }
+  pub fn as_object(&self) -> Option<ObjRef> {
+    match self.kind() {
+      ValueKind::Nil | ValueKind::Boolean(_) | ValueKind::Number(_) => None,
+      ValueKind::String(object)
+      | ValueKind::Closure(object)
+      | ValueKind::NativeFunction(object)
+      | ValueKind::Class(object)
+      | ValueKind::Instance(object)
+      | ValueKind::BoundMethod(object) => Some(object),
+    }
+  }
+}
@@ -0,0 +1,307 @@
+use std::collections::BTreeSet;
+
+use super::chunk::{Capture, Chunk, Constant, FunctionProto, OpCode, Operand, MAX_FUNCTION_DEPTH};
+use crate::diagnostics::Diagnostic;
+
+/*
+checks bytecode that did not come from our own compiler (a `.hexc` file, or an
+assembled `.hexasm` one) before the vm runs it, so a corrupt or hand-written file
+is rejected with diagnostics instead of panicking mid-execution.
+
+every function is checked on its own, in two passes:
+
+  1. decode: every opcode is known, no instruction is cut off by the end of the
+     chunk, constant and name operands point at constants of the right kind, upvalue
+     operands and captures exist in the closure, and jumps land on the start of an
+     instruction inside the chunk.
+  2. stack: the depth of the value stack is tracked along every path, starting from
+     the callee and its parameters. no instruction may pop more than is there, locals
+     (including the ones a closure captures) must refer to existing slots, paths that
+     meet must agree on the depth, a captured slot may only leave the stack through
+     `OP_CLOSE_UPVALUE`, and no path may run off the end of the chunk without returning.
+
+the checks are structural; the type errors the vm already reports at runtime
+(calling a number, adding a string to nil...) are left to it.
+*/
+
+pub fn verify(script: &FunctionProto) -> Result<(), Vec<Diagnostic>> {
+  let mut diagnostics = vec![];
+  if !script.upvalues.is_empty() {
+    let diagnostic = Diagnostic::error("The script can't capture variables.", 0, "").with_note("in <script>");
+    diagnostics.push(diagnostic);
+  }
+  // the vm pushes only the script closure, so the script's frame has no parameter slots.
+  if script.arity != 0 {
+    let diagnostic = Diagnostic::error("The script can't take parameters.", 0, "").with_note("in <script>");
+    diagnostics.push(diagnostic);
+  }
+  verify_function(script, 0, &mut diagnostics);
+  match diagnostics.is_empty() {
+    true => Ok(()),
+    false => Err(diagnostics),
+  }
+}
+
+// `depth` counts the functions enclosing this one.
+fn verify_function(function: &FunctionProto, depth: usize, diagnostics: &mut Vec<Diagnostic>) {
+  if depth == MAX_FUNCTION_DEPTH {
+    let note = format!("in {}", function);
+    diagnostics.push(Diagnostic::error("Functions nested too deeply.", 0, "").with_note(&note));
+    return;
+  }
+  let mut verifier = FunctionVerifier { function, chunk: &function.chunk, diagnostics };
+  if verifier.decode() {
+    verifier.track_stack();
+  }
+  for constant in &function.chunk.constants {
+    if let Constant::Function(nested) = constant {
+      verify_function(nested, depth + 1, diagnostics);
+    }
+  }
+}
+
+struct FunctionVerifier<'a> {
+  function: &'a FunctionProto,
+  chunk: &'a Chunk,
+  diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl<'a> FunctionVerifier<'a> {
+  // pass 1; false when the chunk can't be walked safely.
+  fn decode(&mut self) -> bool {
+    let length = self.chunk.code.len();
+    if self.chunk.lines.len() != length {
+      self.error(0, "Line table doesn't match the code.");
+      return false;
+    }
+    let mut starts = vec![false; length];
+    let mut jumps = vec![];
+    let mut offset = 0;
+    while offset < length {
+      let byte = self.chunk.code[offset];
+      let Some(op) = OpCode::from_byte(byte) else {
+        self.error(offset, &format!("Unknown opcode {}.", byte));
+        return false;
+      };
+      if offset + op.size() > length {
+        self.error(offset, &format!("Truncated {} instruction.", op.name()));
+        return false;
+      }
+      starts[offset] = true;
+      match op.operand() {
+        Operand::Constant | Operand::Name | Operand::Invoke => self.check_constant(offset, op),
+        Operand::Jump => jumps.push((offset, Some(offset + 3 + self.chunk.read_u16(offset + 1) as usize))),
+        Operand::Loop => jumps.push((
+          offset,
+          (offset + 3).checked_sub(self.chunk.read_u16(offset + 1) as usize),
+        )),
+        Operand::Upvalue => self.check_upvalue(offset),
+        Operand::None | Operand::Slot | Operand::ArgumentCount => {}
+      }
+      offset += op.size();
+    }
+
+    let errors = self.diagnostics.len();
+    for (offset, target) in jumps {
+      match target {
+        Some(target) if target < length && starts[target] => {}
+        Some(target) if target < length => self.error(offset, "Jump into the middle of an instruction."),
+        _ => self.error(offset, "Jump target out of bounds."),
+      }
+    }
+    self.diagnostics.len() == errors
+  }
+
+  fn check_constant(&mut self, offset: usize, op: OpCode) {
+    let index = self.chunk.code[offset + 1] as usize;
+    let Some(constant) = self.chunk.constants.get(index) else {
+      return self.error(offset, &format!("Constant index {} out of range.", index));
+    };
+    match (op.operand(), op, constant) {
+      (Operand::Name | Operand::Invoke, _, Constant::String(_)) => {}
+      (Operand::Name | Operand::Invoke, _, _) => {
+        self.error(offset, &format!("{} expects a string constant.", op.name()))
+      }
+      (_, OpCode::Closure, Constant::Function(function)) => {
+        for capture in &function.upvalues {
+          if let Capture::Upvalue(index) = capture {
+            if *index as usize >= self.function.upvalues.len() {
+              self.error(offset, &format!("Captured upvalue index {} out of range.", index));
+            }
+          }
+        }
+      }
+      (_, OpCode::Closure, _) => self.error(offset, "OP_CLOSURE expects a function constant."),
+      (_, OpCode::Constant, Constant::Function(function)) if !function.upvalues.is_empty() => {
+        self.error(offset, "OP_CONSTANT can't create a closure that captures variables.")
+      }
+      _ => {}
+    }
+  }
+
+  fn check_upvalue(&mut self, offset: usize) {
+    let index = self.chunk.code[offset + 1];
+    if index as usize >= self.function.upvalues.len() {
+      self.error(offset, &format!("Upvalue index {} out of range.", index));
+    }
+  }
+
+  // pass 2, only run on a chunk that decoded cleanly.
+  fn track_stack(&mut self) {
+    let length = self.chunk.code.len();
+    if length == 0 {
+      return self.error(0, "Execution can fall off the end of the function.");
+    }
+    // the callee (or receiver) and the arguments are already on the stack, and nothing is captured yet.
+    let mut states: Vec<Option<(usize, BTreeSet<usize>)>> = vec![None; length];
+    let mut pending = vec![(0, self.function.arity + 1, BTreeSet::new())];
+    while let Some((offset, depth, mut captured)) = pending.pop() {
+      if let Some((known, known_captured)) = &states[offset] {
+        if *known != depth {
+          let message = format!(
+            "Inconsistent stack depth: {} on one path and {} on another.",
+            known, depth
+          );
+          self.error(offset, &message);
+          return;
+        }
+        // a slot captured on any path into an instruction counts as captured there.
+        if captured.is_subset(known_captured) {
+          continue;
+        }
+        captured.extend(known_captured);
+      }
+      states[offset] = Some((depth, captured.clone()));
+
+      let op = OpCode::from_byte(self.chunk.code[offset]).expect("decoded in the first pass");
+      // the argument count of `OP_INVOKE` follows its name.
+      let operand = match op {
+        OpCode::Invoke => offset + 2,
+        _ => offset + 1,
+      };
+      let operand = self.chunk.code.get(operand).copied().unwrap_or(0) as usize;
+      let (pops, pushes) = stack_effect(op, operand);
+      if depth < pops {
+        self.error(
+          offset,
+          &format!(
+            "Stack underflow: {} needs {} values but has {}.",
+            op.name(),
+            pops,
+            depth
+          ),
+        );
+        return;
+      }
+      if matches!(op, OpCode::GetLocal | OpCode::SetLocal) && operand >= depth {
+        self.error(offset, &format!("Local slot {} out of range.", operand));
+        return;
+      }
+      if op == OpCode::Closure && !self.captures_fit(offset, depth, &mut captured) {
+        return;
+      }
+      if !self.drops_closed(offset, op, depth - pops + pushes, &mut captured) {
+        return;
+      }
+      let depth = depth - pops + pushes;
+
+      let next = offset + op.size();
+      let successors = match op {
+        OpCode::Return => vec![],
+        OpCode::Jump => vec![next + self.chunk.read_u16(offset + 1) as usize],
+        OpCode::JumpIfFalse => vec![next, next + self.chunk.read_u16(offset + 1) as usize],
+        OpCode::Loop => vec![next - self.chunk.read_u16(offset + 1) as usize],
+        _ => vec![next],
+      };
+      for successor in successors {
+        if successor >= length {
+          self.error(offset, "Execution can fall off the end of the function.");
+          return;
+        }
+        pending.push((successor, depth, captured.clone()));
+      }
+    }
+  }
+
+  // the locals a closure captures have to exist in the frame creating it; they are added to `captured`.
+  fn captures_fit(&mut self, offset: usize, depth: usize, captured: &mut BTreeSet<usize>) -> bool {
+    let index = self.chunk.code[offset + 1] as usize;
+    let Some(Constant::Function(function)) = self.chunk.constants.get(index) else {
+      return true;
+    };
+    for capture in &function.upvalues {
+      if let Capture::Local(slot) = capture {
+        if *slot as usize >= depth {
+          self.error(offset, &format!("Captured local slot {} out of range.", slot));
+          return false;
+        }
+        captured.insert(*slot as usize);
+      }
+    }
+    true
+  }
+
+  // a captured slot may only leave the stack through `OP_CLOSE_UPVALUE`, which moves it into its upvalue
+  // first. a call's arguments are closed when the callee returns. `OP_RETURN` closes the rest of the
+  // frame, but pops the value it returns beforehand, so that one must not be captured.
+  fn drops_closed(&mut self, offset: usize, op: OpCode, depth_after: usize, captured: &mut BTreeSet<usize>) -> bool {
+    let dropped = captured.split_off(&depth_after);
+    let unclosed = match op {
+      OpCode::CloseUpvalue | OpCode::Call | OpCode::Invoke => None,
+      _ => dropped.first(),
+    };
+    let Some(slot) = unclosed else {
+      return true;
+    };
+    self.error(
+      offset,
+      &format!("{} drops captured local slot {} without closing it.", op.name(), slot),
+    );
+    false
+  }
+  fn error(&mut self, offset: usize, message: &str) {
+    let line = self.chunk.lines.get(offset).copied().unwrap_or(0);
+    let note = format!("in {} at offset {:04}", self.function, offset);
+    self
+      .diagnostics
+      .push(Diagnostic::error(message, line, "").with_note(&note));
+  }
+}
+
+// how many values an instruction pops, then pushes.
+fn stack_effect(op: OpCode, operand: usize) -> (usize, usize) {
+  match op {
+    OpCode::Constant
+    | OpCode::Nil
+    | OpCode::True
+    | OpCode::False
+    | OpCode::GetLocal
+    | OpCode::GetGlobal
+    | OpCode::GetUpvalue
+    | OpCode::Closure
+    | OpCode::Class => (0, 1),
+    OpCode::Pop | OpCode::DefineGlobal | OpCode::Print | OpCode::Return | OpCode::CloseUpvalue => (1, 0),
+    OpCode::SetLocal
+    | OpCode::SetGlobal
+    | OpCode::SetUpvalue
+    | OpCode::GetProperty
+    | OpCode::Not
+    | OpCode::Negate
+    | OpCode::JumpIfFalse => (1, 1),
+    OpCode::SetProperty
+    | OpCode::GetSuper
+    | OpCode::Equal
+    | OpCode::Greater
+    | OpCode::GreaterEqual
+    | OpCode::Less
+    | OpCode::LessEqual
+    | OpCode::Add
+    | OpCode::Subtract
+    | OpCode::Multiply
+    | OpCode::Divide
+    | OpCode::Inherit
+    | OpCode::Method => (2, 1),
+    OpCode::Jump | OpCode::Loop => (0, 0),
+    OpCode::Call | OpCode::Invoke => (operand + 1, 1),
+  }
+}
@@ -0,0 +1,749 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use super::chunk::{Capture, Constant, FunctionProto, OpCode};
+use super::disassembler::disassemble_instruction;
+use super::heap::{GcStats, Heap, ObjRef};
+use super::inline_cache::{CacheStats, CacheTarget, FunctionCaches, InlineCache};
+use super::object::{clock, BoundMethod, Class, Closure, Instance, NativeFunction, Object, Upvalue};
+use super::shape::Shapes;
+use super::value::{Value, ValueKind};
+use crate::ast::Span;
+use crate::diagnostics::Diagnostic;
+use crate::shared::constants::MAX_CALL_DEPTH;
+use crate::shared::options::RunOptions;
+use crate::shared::output::Output;
+use crate::shared::symbol::Symbol;
+use crate::values::Truthiness;
+
+// how many of the innermost and of the outermost calls a runtime error lists; the rest are counted.
+const TRACE_EDGE_FRAMES: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct VmError {
+  pub message: String,
+  pub line: usize,
+  pub span: Option<Span>, // none for code that didn't come from the compiler
+  pub trace: Vec<String>, // "[line N] in name()" per active call, innermost first, with the middle elided
+}
+
+impl VmError {
+  // the call trace becomes notes.
+  pub fn to_diagnostic(&self) -> Diagnostic {
+    let mut diagnostic = Diagnostic::error(&self.message, self.line, "");
+    if let Some(span) = self.span {
+      diagnostic = diagnostic.with_span(span);
+    }
+    for entry in &self.trace {
+      diagnostic = diagnostic.with_note(entry);
+    }
+    diagnostic
+  }
+}
+
+impl Display for VmError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}\n[line {}]", self.message, self.line)
+  }
+}
+
+pub type VmResult<T> = Result<T, VmError>;
+
+// what `--stats` reports after a run.
+#[derive(Debug, Clone, Copy)]
+pub struct VmStats {
+  pub caches: CacheStats,
+  pub shapes: usize, // instance shapes in use
+  pub gc: GcStats,
+  pub live_objects: usize,
+}
+
+struct CallFrame {
+  closure: ObjRef,
+  function: Rc<FunctionProto>, // the closure's, kept here to read code without going through the heap
+  ip: usize,
+  slots: usize,  // index of the frame's slot 0 in the vm stack
+  caches: usize, // the closure's, for the same reason
+}
+
+pub struct Vm {
+  pub truthiness: Truthiness,
+  pub output: Output,
+  pub trace: bool, // print the stack and each instruction to stderr before it executes
+  pub heap: Heap,
+  stack: Vec<Value>,
+  frames: Vec<CallFrame>,
+  globals: HashMap<Symbol, Value>,
+  open_upvalues: Vec<ObjRef>, // upvalues still pointing into the stack, at most one per slot
+  shapes: Shapes,
+  caches: Vec<FunctionCaches>,
+  cache_indexes: HashMap<*const FunctionProto, usize>, // which entry of `caches` belongs to a function
+  method_epoch: u32, // bumped whenever a class gains methods or is freed, which empties every inline cache
+  cache_stats: CacheStats,
+  init: Symbol, // the name of initializers, looked up on every instantiation
+}
+
+impl Vm {
+  pub fn new() -> Self {
+    let mut vm = Vm {
+      truthiness: Truthiness::default(),
+      output: Output::Stdout,
+      trace: false,
+      heap: Heap::new(),
+      stack: Vec::with_capacity(256),
+      frames: Vec::with_capacity(64),
+      globals: HashMap::new(),
+      open_upvalues: vec![],
+      shapes: Shapes::new(),
+      caches: vec![],
+      cache_indexes: HashMap::new(),
+      method_epoch: 0,
+      cache_stats: CacheStats::default(),
+      init: Symbol::intern("init"),
+    };
+    vm.define_native("clock", 0, clock);
+    vm
+  }
+
+  pub fn with_options(options: &RunOptions) -> Self {
+    let mut vm = Vm::new();
+    vm.truthiness = options.truthiness;
+    vm.trace = options.trace;
+    vm.heap.stress = options.gc_stress;
+    if let Some(growth_factor) = options.gc_growth_factor {
+      vm.heap.growth_factor = growth_factor;
+    }
+    vm
+  }
+
+  fn define_native(&mut self, name: &str, arity: usize, function: fn(&[Value]) -> Value) {
+    let native = self.allocate(Object::NativeFunction(NativeFunction::new(arity, function)));
+    self
+      .globals
+      .insert(Symbol::intern(name), Value::native_function(native));
+  }
+
+  pub fn stats(&self) -> VmStats {
+    VmStats {
+      caches: self.cache_stats,
+      shapes: self.shapes.len(),
+      gc: self.heap.stats,
+      live_objects: self.heap.live_objects(),
+    }
+  }
+
+  // runs a compiled script; globals survive between calls.
+  pub fn interpret(&mut self, script: Rc<FunctionProto>) -> VmResult<()> {
+    let closure = self.new_closure(Rc::clone(&script));
+    self.stack.push(Value::closure(closure));
+    let caches = self.heap.closure(closure).caches;
+    self
+      .frames
+      .push(CallFrame { closure, function: script, ip: 0, slots: 0, caches });
+    let result = self.run();
+    if result.is_err() {
+      self.stack.clear();
+      self.frames.clear();
+      self.open_upvalues.clear();
+    }
+    result
+  }
+
+  fn run(&mut self) -> VmResult<()> {
+    loop {
+      if self.trace {
+        self.trace_instruction();
+      }
+      let byte = self.read_byte();
+      let Some(op) = OpCode::from_byte(byte) else {
+        return Err(self.error(&format!("Unknown opcode {}.", byte)));
+      };
+      match op {
+        OpCode::Constant => {
+          let value = match self.read_constant() {
+            Constant::Number(number) => Value::number(number),
+            Constant::String(string) => Value::string(self.intern(&string)),
+            Constant::Function(function) => Value::closure(self.new_closure(function)),
+          };
+          self.push(value);
+        }
+        OpCode::Nil => self.push(Value::nil()),
+        OpCode::True => self.push(Value::boolean(true)),
+        OpCode::False => self.push(Value::boolean(false)),
+        OpCode::Pop => {
+          self.pop();
+        }
+        OpCode::GetLocal => {
+          let slot = self.read_byte() as usize;
+          let value = self.stack[self.frame().slots + slot];
+          self.push(value);
+        }
+        OpCode::SetLocal => {
+          let slot = self.read_byte() as usize;
+          let index = self.frame().slots + slot;
+          self.stack[index] = *self.peek(0);
+        }
+        OpCode::GetGlobal => {
+          let name = self.read_string();
+          let Some(value) = self.globals.get(&name).copied() else {
+            return Err(self.error(&format!("Undefined variable '{}'.", name)));
+          };
+          self.push(value);
+        }
+        OpCode::DefineGlobal => {
+          let name = self.read_string();
+          let value = self.pop();
+          self.globals.insert(name, value);
+        }
+        OpCode::SetGlobal => {
+          let name = self.read_string();
+          let value = *self.peek(0);
+          let Some(slot) = self.globals.get_mut(&name) else {
+            return Err(self.error(&format!("Undefined variable '{}'.", name)));
+          };
+          *slot = value;
+        }
+        OpCode::GetUpvalue => {
+          let index = self.read_byte() as usize;
+          let upvalue = self.heap.closure(self.frame().closure).upvalues[index];
+          let value = match self.heap.upvalue(upvalue) {
+            Upvalue::Open(slot) => *self.open_slot(*slot)?,
+            Upvalue::Closed(value) => *value,
+          };
+          self.push(value);
+        }
+        OpCode::SetUpvalue => {
+          let index = self.read_byte() as usize;
+          let upvalue = self.heap.closure(self.frame().closure).upvalues[index];
+          let value = *self.peek(0);
+          match *self.heap.upvalue(upvalue) {
+            Upvalue::Open(slot) => *self.open_slot_mut(slot)? = value,
+            Upvalue::Closed(_) => *self.heap.upvalue_mut(upvalue) = Upvalue::Closed(value),
+          }
+        }
+        OpCode::GetProperty => {
+          let offset = self.frame().ip - 1;
+          let name = self.read_string();
+          let ValueKind::Instance(instance) = self.peek(0).kind() else {
+            return Err(self.error("Only instances have properties."));
+          };
+          match self.find_property(op, offset, instance, &name) {
+            Some(CacheTarget::Field(index)) => {
+              let value = self.heap.instance(instance).fields[index];
+              self.pop();
+              self.push(value);
+            }
+            Some(CacheTarget::Method(method)) => self.bind(method, 1),
+            _ => return Err(self.error(&format!("Undefined property '{}'.", name))),
+          }
+        }
+        OpCode::SetProperty => {
+          let offset = self.frame().ip - 1;
+          let name = self.read_string();
+          let ValueKind::Instance(instance) = self.peek(1).kind() else {
+            return Err(self.error("Only instances have fields."));
+          };
+          let value = self.pop();
+          let shape = self.heap.instance(instance).shape;
+          let cached = self.inline_cache(offset).lookup(shape);
+          self.cache_stats.record(op, cached.is_some());
+          let target = match cached {
+            Some(target) => target,
+            None => {
+              let target = match self.shapes.field(shape, &name) {
+                Some(index) => CacheTarget::Field(index),
+                None => CacheTarget::NewField(self.shapes.with_field(shape, &name)),
+              };
+              self.inline_cache(offset).insert(shape, target);
+              target
+            }
+          };
+          let instance = self.heap.instance_mut(instance);
+          match target {
+            CacheTarget::Field(index) => instance.fields[index] = value,
+            CacheTarget::NewField(shape) => {
+              instance.fields.push(value);
+              instance.shape = shape;
+            }
+            CacheTarget::Method(_) => unreachable!("assignments only cache fields"),
+          }
+          self.pop();
+          self.push(value);
+        }
+        OpCode::GetSuper => {
+          let name = self.read_string();
+          let ValueKind::Class(superclass) = self.peek(0).kind() else {
+            return Err(self.error("Superclass must be a class."));
+          };
+          self.bind_method(superclass, &name, 2)?;
+        }
+        OpCode::Equal => {
+          let right = self.pop();
+          let left = self.pop();
+          self.push(Value::boolean(left.equals(&right)));
+        }
+        OpCode::Greater => self.comparison(|left, right| left > right)?,
+        OpCode::GreaterEqual => self.comparison(|left, right| left >= right)?,
+        OpCode::Less => self.comparison(|left, right| left < right)?,
+        OpCode::LessEqual => self.comparison(|left, right| left <= right)?,
+        OpCode::Add => {
+          let right = self.pop();
+          let left = self.pop();
+          let result = match (left.kind(), right.kind()) {
+            (ValueKind::Number(left), ValueKind::Number(right)) => Value::number(left + right),
+            (ValueKind::String(left), ValueKind::String(right)) => {
+              let concatenated = format!("{}{}", self.heap.string(left), self.heap.string(right));
+              Value::string(self.intern(&concatenated))
+            }
+            _ => return Err(self.error("Operands must be two numbers or two strings.")),
+          };
+          self.push(result);
+        }
+        OpCode::Subtract => self.arithmetic(|left, right| left - right)?,
+        OpCode::Multiply => self.arithmetic(|left, right| left * right)?,
+        OpCode::Divide => self.arithmetic(|left, right| left / right)?,
+        OpCode::Not => {
+          let value = self.pop();
+          let truthy = value.is_truthy_with(self.truthiness, &self.heap);
+          self.push(Value::boolean(!truthy));
+        }
+        OpCode::Negate => {
+          if !self.peek(0).is_number() {
+            return Err(self.error("Operand must be a number."));
+          }
+          let number = self.pop().as_number();
+          self.push(Value::number(-number));
+        }
+        OpCode::Print => {
+          let value = self.pop();
+          let text = self.heap.format(&value);
+          self.output.print_line(&text);
+        }
+        OpCode::Jump => {
+          let offset = self.read_u16() as usize;
+          self.frame_mut().ip += offset;
+        }
+        OpCode::JumpIfFalse => {
+          let offset = self.read_u16() as usize;
+          if !self.peek(0).is_truthy_with(self.truthiness, &self.heap) {
+            self.frame_mut().ip += offset;
+          }
+        }
+        OpCode::Loop => {
+          let offset = self.read_u16() as usize;
+          self.frame_mut().ip -= offset;
+        }
+        OpCode::Call => {
+          let argument_count = self.read_byte() as usize;
+          let callee = *self.peek(argument_count);
+          self.call_value(callee, argument_count)?;
+        }
+        OpCode::Invoke => {
+          let offset = self.frame().ip - 1;
+          let name = self.read_string();
+          let argument_count = self.read_byte() as usize;
+          let ValueKind::Instance(instance) = self.peek(argument_count).kind() else {
+            return Err(self.error("Only instances have properties."));
+          };
+          // a field holding a function shadows a method, and is called without binding `this`.
+          match self.find_property(op, offset, instance, &name) {
+            Some(CacheTarget::Field(index)) => {
+              let callee = self.heap.instance(instance).fields[index];
+              let slot = self.stack.len() - argument_count - 1;
+              self.stack[slot] = callee;
+              self.call_value(callee, argument_count)?;
+            }
+            Some(CacheTarget::Method(method)) => self.call(method, argument_count)?,
+            _ => return Err(self.error(&format!("Undefined property '{}'.", name))),
+          }
+        }
+        OpCode::Closure => {
+          let Constant::Function(function) = self.read_constant() else {
+            return Err(self.error("Closure operand must be a function constant."));
+          };
+          // the closure goes on the stack first, so the upvalues allocated below can't collect it.
+          let closure = self.new_closure(Rc::clone(&function));
+          self.push(Value::closure(closure));
+          for capture in &function.upvalues {
+            let upvalue = match *capture {
+              Capture::Local(slot) => self.capture_upvalue(self.frame().slots + slot as usize),
+              Capture::Upvalue(index) => self.heap.closure(self.frame().closure).upvalues[index as usize],
+            };
+            self.heap.closure_mut(closure).upvalues.push(upvalue);
+          }
+        }
+        OpCode::CloseUpvalue => {
+          self.close_upvalues(self.stack.len() - 1)?;
+          self.pop();
+        }
+        OpCode::Return => {
+          let result = self.pop();
+          self.close_upvalues(self.frame().slots)?;
+          let frame = self.frames.pop().expect("returning without a call frame");
+          if self.frames.is_empty() {
+            self.stack.clear();
+            return Ok(());
+          }
+          self.stack.truncate(frame.slots);
+          self.push(result);
+        }
+        OpCode::Class => {
+          let name = self.read_string();
+          let shape = self.shapes.new_root();
+          let class = self.allocate(Object::Class(Class::new(name, shape)));
+          self.push(Value::class(class));
+        }
+        OpCode::Inherit => {
+          let ValueKind::Class(superclass) = self.peek(1).kind() else {
+            return Err(self.error("Superclass must be a class."));
+          };
+          // the compiler never gets this wrong, but hand-written bytecode can.
+          let ValueKind::Class(subclass) = self.pop().kind() else {
+            return Err(self.error("Only classes can inherit."));
+          };
+          let methods = self.heap.class(superclass).methods.clone();
+          self.heap.class_mut(subclass).methods.extend(methods);
+          self.method_epoch = self.method_epoch.wrapping_add(1);
+        }
+        OpCode::Method => {
+          let name = self.read_string();
+          let ValueKind::Closure(method) = self.pop().kind() else {
+            return Err(self.error("Methods must be functions."));
+          };
+          let ValueKind::Class(class) = self.peek(0).kind() else {
+            return Err(self.error("Methods can only be added to classes."));
+          };
+          self.heap.class_mut(class).methods.insert(name, method);
+          self.method_epoch = self.method_epoch.wrapping_add(1);
+        }
+      }
+    }
+  }
+
+  // calls
+
+  fn call_value(&mut self, callee: Value, argument_count: usize) -> VmResult<()> {
+    match callee.kind() {
+      ValueKind::Closure(closure) => self.call(closure, argument_count),
+      ValueKind::NativeFunction(native) => {
+        let native = self.heap.native_function(native);
+        let (arity, function) = (native.arity, native.function);
+        if argument_count != arity {
+          return Err(self.arity_error(arity, argument_count));
+        }
+        let arguments_start = self.stack.len() - argument_count;
+        let result = function(&self.stack[arguments_start..]);
+        self.stack.truncate(arguments_start - 1);
+        self.push(result);
+        Ok(())
+      }
+      ValueKind::Class(class) => {
+        // the instance takes the class's slot, becoming `this` for the initializer.
+        let slot = self.stack.len() - argument_count - 1;
+        let shape = self.heap.class(class).shape;
+        let instance = self.allocate(Object::Instance(Instance::new(class, shape)));
+        self.stack[slot] = Value::instance(instance);
+        let initializer = self.heap.class(class).methods.get(&self.init).copied();
+        match initializer {
+          Some(initializer) => self.call(initializer, argument_count),
+          None if argument_count != 0 => Err(self.arity_error(0, argument_count)),
+          None => Ok(()),
+        }
+      }
+      ValueKind::BoundMethod(bound_method) => {
+        let bound_method = self.heap.bound_method(bound_method);
+        let (receiver, method) = (bound_method.receiver, bound_method.method);
+        let slot = self.stack.len() - argument_count - 1;
+        self.stack[slot] = receiver;
+        self.call(method, argument_count)
+      }
+      _ => Err(self.error("Can only call functions and classes.")),
+    }
+  }
+
+  fn call(&mut self, closure: ObjRef, argument_count: usize) -> VmResult<()> {
+    let (function, caches) = {
+      let closure = self.heap.closure(closure);
+      (Rc::clone(&closure.function), closure.caches)
+    };
+    if argument_count != function.arity {
+      return Err(self.arity_error(function.arity, argument_count));
+    }
+    if self.frames.len() >= MAX_CALL_DEPTH {
+      return Err(self.error("Stack overflow."));
+    }
+    let slots = self.stack.len() - argument_count - 1;
+    self.frames.push(CallFrame { closure, function, ip: 0, slots, caches });
+    Ok(())
+  }
+
+  // replaces the top `operands` values, the receiver first, with one of `class`'s methods bound to the receiver.
+  fn bind_method(&mut self, class: ObjRef, name: &Symbol, operands: usize) -> VmResult<()> {
+    let Some(method) = self.heap.class(class).methods.get(name).copied() else {
+      return Err(self.error(&format!("Undefined property '{}'.", name)));
+    };
+    self.bind(method, operands);
+    Ok(())
+  }
+
+  // the operands stay on the stack until the bound method is allocated, so a collection can't free them.
+  fn bind(&mut self, method: ObjRef, operands: usize) {
+    let receiver = *self.peek(operands - 1);
+    let bound_method = self.allocate(Object::BoundMethod(BoundMethod::new(receiver, method)));
+    self.stack.truncate(self.stack.len() - operands);
+    self.push(Value::bound_method(bound_method));
+  }
+
+  // properties
+
+  // what `name` means on `instance` for the property instruction at `offset`: a field, or else a method.
+  // only the instance's shape decides, so the answer is cached on the instruction.
+  fn find_property(&mut self, op: OpCode, offset: usize, instance: ObjRef, name: &Symbol) -> Option<CacheTarget> {
+    let instance = self.heap.instance(instance);
+    let (shape, class) = (instance.shape, instance.class);
+    let cached = self.inline_cache(offset).lookup(shape);
+    self.cache_stats.record(op, cached.is_some());
+    if cached.is_some() {
+      return cached;
+    }
+    let target = match self.shapes.field(shape, name) {
+      Some(index) => CacheTarget::Field(index),
+      None => CacheTarget::Method(self.heap.class(class).methods.get(name).copied()?),
+    };
+    self.inline_cache(offset).insert(shape, target);
+    Some(target)
+  }
+
+  fn inline_cache(&mut self, offset: usize) -> &mut InlineCache {
+    let frame = self.frames.last().expect("no active call frame");
+    self.caches[frame.caches].at(offset, self.method_epoch)
+  }
+
+  // a closure with no captures yet; functions get their inline caches the first time one is created.
+  fn new_closure(&mut self, function: Rc<FunctionProto>) -> ObjRef {
+    let key = Rc::as_ptr(&function);
+    let caches = match self.cache_indexes.get(&key) {
+      Some(index) if self.caches[*index].belongs_to(&function) => *index,
+      _ => {
+        self
+          .caches
+          .push(FunctionCaches::new(Rc::clone(&function), self.method_epoch));
+        self.cache_indexes.insert(key, self.caches.len() - 1);
+        self.caches.len() - 1
+      }
+    };
+    self.allocate(Object::Closure(Closure::new(function, caches)))
+  }
+
+  // upvalues
+
+  // the upvalue for a stack slot, shared with every other closure that captured the same slot.
+  fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+    let existing = self
+      .open_upvalues
+      .iter()
+      .copied()
+      .find(|upvalue| match self.heap.upvalue(*upvalue) {
+        Upvalue::Open(open) => *open == slot,
+        Upvalue::Closed(_) => false,
+      });
+    if let Some(upvalue) = existing {
+      return upvalue;
+    }
+    let upvalue = self.allocate(Object::Upvalue(Upvalue::Open(slot)));
+    self.open_upvalues.push(upvalue);
+    upvalue
+  }
+
+  // moves every variable from `first_slot` up off the stack and into its upvalue, before those slots are popped.
+  fn close_upvalues(&mut self, first_slot: usize) -> Result<(), VmError> {
+    let mut index = 0;
+    while index < self.open_upvalues.len() {
+      let upvalue = self.open_upvalues[index];
+      let Upvalue::Open(slot) = *self.heap.upvalue(upvalue) else {
+        unreachable!("only open upvalues are tracked");
+      };
+      if slot < first_slot {
+        index += 1;
+        continue;
+      }
+      let value = *self.open_slot(slot)?;
+      *self.heap.upvalue_mut(upvalue) = Upvalue::Closed(value);
+      self.open_upvalues.swap_remove(index);
+    }
+    Ok(())
+  }
+
+  // the verifier keeps captured slots on the stack until they are closed; this guards against it missing a case.
+  fn open_slot(&self, slot: usize) -> Result<&Value, VmError> {
+    self.stack.get(slot).ok_or_else(|| self.lost_slot(slot))
+  }
+
+  fn open_slot_mut(&mut self, slot: usize) -> Result<&mut Value, VmError> {
+    if slot >= self.stack.len() {
+      return Err(self.lost_slot(slot));
+    }
+    Ok(&mut self.stack[slot])
+  }
+
+  fn lost_slot(&self, slot: usize) -> VmError {
+    self.error(&format!("Captured stack slot {} is no longer on the stack.", slot))
+  }
+
+  // memory
+
+  fn allocate(&mut self, object: Object) -> ObjRef {
+    if self.heap.should_collect() {
+      self.collect_garbage();
+    }
+    self.heap.allocate(object)
+  }
+
+  fn intern(&mut self, string: &str) -> ObjRef {
+    if let Some(interned) = self.heap.find_string(string) {
+      return interned;
+    }
+    if self.heap.should_collect() {
+      self.collect_garbage();
+    }
+    self.heap.allocate_string(string)
+  }
+
+  fn collect_garbage(&mut self) {
+    let values = self
+      .stack
+      .iter()
+      .chain(self.globals.values())
+      .filter_map(Value::as_object);
+    let frames = self.frames.iter().map(|frame| frame.closure);
+    let roots: Vec<ObjRef> = values.chain(frames).chain(self.open_upvalues.iter().copied()).collect();
+    let freed_shapes = self.heap.collect(roots);
+    if freed_shapes.is_empty() {
+      return;
+    }
+    // caches may still name the freed shapes, or methods that died with their class.
+    for root in freed_shapes {
+      self.shapes.release(root);
+    }
+    self.method_epoch = self.method_epoch.wrapping_add(1);
+  }
+
+  // operators
+
+  fn arithmetic(&mut self, operation: fn(f64, f64) -> f64) -> VmResult<()> {
+    let (left, right) = self.number_operands()?;
+    self.push(Value::number(operation(left, right)));
+    Ok(())
+  }
+
+  fn comparison(&mut self, operation: fn(f64, f64) -> bool) -> VmResult<()> {
+    let (left, right) = self.number_operands()?;
+    self.push(Value::boolean(operation(left, right)));
+    Ok(())
+  }
+
+  fn number_operands(&mut self) -> VmResult<(f64, f64)> {
+    if !self.peek(0).is_number() || !self.peek(1).is_number() {
+      return Err(self.error("Operands must be numbers."));
+    }
+    let right = self.pop().as_number();
+    let left = self.pop().as_number();
+    Ok((left, right))
+  }
+
+  // stack
+
+  fn push(&mut self, value: Value) {
+    self.stack.push(value);
+  }
+
+  fn pop(&mut self) -> Value {
+    self.stack.pop().expect("vm stack underflow")
+  }
+
+  fn peek(&self, distance: usize) -> &Value {
+    &self.stack[self.stack.len() - 1 - distance]
+  }
+
+  // reading the current instruction stream
+
+  fn frame(&self) -> &CallFrame {
+    self.frames.last().expect("no active call frame")
+  }
+
+  fn frame_mut(&mut self) -> &mut CallFrame {
+    self.frames.last_mut().expect("no active call frame")
+  }
+
+  fn read_byte(&mut self) -> u8 {
+    let frame = self.frame_mut();
+    let byte = frame.function.chunk.code[frame.ip];
+    frame.ip += 1;
+    byte
+  }
+
+  fn read_u16(&mut self) -> u16 {
+    let frame = self.frame_mut();
+    let value = frame.function.chunk.read_u16(frame.ip);
+    frame.ip += 2;
+    value
+  }
+
+  fn read_constant(&mut self) -> Constant {
+    let index = self.read_byte() as usize;
+    self.frame().function.chunk.constants[index].clone()
+  }
+
+  // a name operand; cloning the symbol only bumps a reference count.
+  fn read_string(&mut self) -> Symbol {
+    match self.read_constant() {
+      Constant::String(string) => string,
+      _ => unreachable!("the compiler and the verifier only allow string constants as names"),
+    }
+  }
+
+  fn trace_instruction(&self) {
+    let stack: String = self
+      .stack
+      .iter()
+      .map(|value| format!("[ {} ]", self.heap.format(value)))
+      .collect();
+    eprintln!("          {}", stack);
+    let frame = self.frame();
+    let (instruction, _) = disassemble_instruction(&frame.function.chunk, frame.ip);
+    eprintln!("{}", instruction);
+  }
+
+  // errors
+
+  fn arity_error(&self, expected: usize, got: usize) -> VmError {
+    self.error(&format!("Expected {} arguments but got {}.", expected, got))
+  }
+
+  // reported at the line of the instruction being executed, with the active calls as a trace.
+  fn error(&self, message: &str) -> VmError {
+    let entry = |frame: &CallFrame| {
+      let line = frame.function.chunk.lines[frame.ip.saturating_sub(1)];
+      match frame.function.name.as_str() {
+        "" => format!("[line {}] in script", line),
+        name => format!("[line {}] in {}()", line, name),
+      }
+    };
+    let frames = self.frames.len();
+    let trace = match frames > 2 * TRACE_EDGE_FRAMES + 1 {
+      true => {
+        let innermost = self.frames.iter().rev().take(TRACE_EDGE_FRAMES).map(entry);
+        let elided = format!("... {} more frames", frames - 2 * TRACE_EDGE_FRAMES);
+        let outermost = self.frames.iter().take(TRACE_EDGE_FRAMES).rev().map(entry);
+        innermost.chain(std::iter::once(elided)).chain(outermost).collect()
+      }
+      false => self.frames.iter().rev().map(entry).collect(),
+    };
+    let frame = self.frame();
+    let offset = frame.ip.saturating_sub(1);
+    let line = frame.function.chunk.lines[offset];
+    let span = frame.function.chunk.span_at(offset);
+    VmError { message: message.to_owned(), line, span, trace }
+  }
+}
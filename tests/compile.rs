@@ -0,0 +1,293 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+// runs `lox <args> -` with `source` on stdin.
+fn lox_with_stdin(args: &[&str], source: &str) -> Output {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+    .args(args)
+    .arg("-")
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .unwrap();
+  child.stdin.take().unwrap().write_all(source.as_bytes()).unwrap();
+  child.wait_with_output().unwrap()
+}
+
+// runs `lox compile -`, which reads the program from stdin and writes bytecode to stdout.
+fn compile(source: &str) -> Output {
+  lox_with_stdin(&["compile"], source)
+}
+
+#[test]
+fn compiles_a_program_to_bytecode() {
+  let output = compile("fun add(a, b) { return a + b; }\nprint add(1, 2);\n");
+  assert!(output.status.success());
+  assert!(output.stdout.starts_with(b"HEXC"));
+}
+
+#[test]
+fn reports_compile_errors_with_exit_code_65() {
+  let output = compile("print 1 +;\n");
+  assert_eq!(output.status.code(), Some(65));
+}
+
+// writes `bytes` to a `.hexc` file in the temp directory and runs it.
+fn run_bytecode(name: &str, bytes: &[u8]) -> Output {
+  let path: PathBuf = std::env::temp_dir().join(format!("lox-{}-{}.hexc", name, std::process::id()));
+  fs::write(&path, bytes).unwrap();
+  let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+    .args(["run", path.to_str().unwrap()])
+    .output()
+    .unwrap();
+  fs::remove_file(&path).unwrap();
+  output
+}
+
+#[test]
+fn runs_a_compiled_file_without_its_source() {
+  let compiled = compile("class Greeter { greet(name) { return \"hi \" + name; } }\nprint Greeter().greet(\"lox\");\n");
+  let output = run_bytecode("run", &compiled.stdout);
+  assert!(output.status.success());
+  assert_eq!(String::from_utf8_lossy(&output.stdout), "hi lox\n");
+}
+
+#[test]
+fn runs_compiled_closures_over_locals() {
+  let source = "fun makeCounter() {\n  var count = 0;\n  fun increment() {\n    count = count + 1;\n    return count;\n  }\n  return increment;\n}\nvar counter = makeCounter();\ncounter();\nprint counter();\n";
+  let compiled = compile(source);
+  assert!(compiled.status.success());
+  let output = run_bytecode("closure", &compiled.stdout);
+  assert!(output.status.success());
+  assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+}
+
+#[test]
+fn rejects_a_mismatched_bytecode_version() {
+  let mut bytes = compile("print 1;\n").stdout;
+  bytes[4] = 0xff;
+  let output = run_bytecode("version", &bytes);
+  assert_eq!(output.status.code(), Some(65));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("Unsupported bytecode version 255."), "{}", stdout);
+}
+
+#[test]
+fn rejects_files_that_are_not_bytecode() {
+  let output = run_bytecode("magic", b"print 1;\n");
+  assert_eq!(output.status.code(), Some(65));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("Not a lox bytecode file."), "{}", stdout);
+}
+
+#[test]
+fn disassembles_every_function_with_constants_and_lines() {
+  let output = lox_with_stdin(&["disasm"], "fun add(a, b) {\n  return a + b;\n}\nprint add(1, 2);\n");
+  assert!(output.status.success());
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("== <script> =="), "{}", stdout);
+  assert!(
+    stdout.contains("0000    1 OP_CLOSURE          0 <fn add>"),
+    "{}",
+    stdout
+  );
+  assert!(stdout.contains("== <fn add> =="), "{}", stdout);
+  assert!(stdout.contains("0000    2 OP_GET_LOCAL        1"), "{}", stdout);
+}
+
+#[test]
+fn disassembles_the_captures_of_a_closure() {
+  let source = "fun outer() {\n  var a = 1;\n  fun inner() {\n    return a;\n  }\n  return inner;\n}\n";
+  let output = lox_with_stdin(&["disasm"], source);
+  assert!(output.status.success());
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(
+    stdout.contains("OP_CLOSURE          1 <fn inner>\n0002    |                  local 1"),
+    "{}",
+    stdout
+  );
+  assert!(stdout.contains("0000    4 OP_GET_UPVALUE      0"), "{}", stdout);
+}
+
+#[test]
+fn disassembles_method_calls_as_invoke() {
+  let output = lox_with_stdin(&["disasm"], "class Foo { bar(a) { return a; } }\nprint Foo().bar(1);\n");
+  assert!(output.status.success());
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(
+    stdout.contains("0017    | OP_INVOKE           2 \"bar\" (1 args)\n0020    | OP_PRINT"),
+    "{}",
+    stdout
+  );
+}
+
+#[test]
+fn reports_inline_cache_hit_rates_with_stats() {
+  let source = "class Point {\n  init(x) { this.x = x; }\n  get() { return this.x; }\n}\nvar total = 0;\nfor (var i = 0; i < 100; i = i + 1) {\n  total = total + Point(i).get();\n}\nprint total;\n";
+  let output = lox_with_stdin(&["--stats", "run"], source);
+  assert!(output.status.success());
+  assert_eq!(String::from_utf8_lossy(&output.stdout), "4950\n");
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  // one miss per instruction, the first time it runs; every other instance has the same shape.
+  assert!(
+    stderr.contains("get property           99 hits          1 misses   99.0% hit rate"),
+    "{}",
+    stderr
+  );
+  assert!(
+    stderr.contains("set property           99 hits          1 misses   99.0% hit rate"),
+    "{}",
+    stderr
+  );
+  assert!(
+    stderr.contains("invoke                 99 hits          1 misses   99.0% hit rate"),
+    "{}",
+    stderr
+  );
+  assert!(stderr.contains("shapes                  2"), "{}", stderr);
+}
+
+#[test]
+fn traces_vm_execution_on_stderr() {
+  let output = lox_with_stdin(&["--backend", "vm", "--trace", "run"], "print 1 + 2;\n");
+  assert!(output.status.success());
+  assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(
+    stderr.contains("          [ <script> ][ 1 ][ 2 ]\n0004    | OP_ADD"),
+    "{}",
+    stderr
+  );
+}
+
+#[test]
+fn assembles_a_hexasm_program_to_bytecode() {
+  let assembled = lox_with_stdin(&["asm"], "  constant \"from asm\"\n  print\n  nil\n  return\n");
+  assert!(assembled.status.success());
+  let output = run_bytecode("asm", &assembled.stdout);
+  assert_eq!(String::from_utf8_lossy(&output.stdout), "from asm\n");
+}
+
+// `print 1;` compiles to OP_CONSTANT 0, OP_PRINT, OP_NIL, OP_RETURN, starting after the
+// 14-byte header, the script's empty name, its arity and the code length.
+const SCRIPT_CODE_START: usize = 14 + 4 + 4 + 4;
+
+#[test]
+fn rejects_a_constant_index_out_of_range() {
+  let mut bytes = compile("print 1;\n").stdout;
+  bytes[SCRIPT_CODE_START + 1] = 200;
+  let output = run_bytecode("constant-index", &bytes);
+  assert_eq!(output.status.code(), Some(65));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("Constant index 200 out of range."), "{}", stdout);
+  assert!(stdout.contains("in <script> at offset 0000"), "{}", stdout);
+}
+
+#[test]
+fn rejects_a_script_with_parameters() {
+  let mut bytes = compile("print 1;\n").stdout;
+  bytes[SCRIPT_CODE_START - 8] = 5; // the script's arity, after its empty name
+  let output = run_bytecode("script-arity", &bytes);
+  assert_eq!(output.status.code(), Some(65));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("The script can't take parameters."), "{}", stdout);
+}
+
+#[test]
+fn rejects_an_unknown_opcode() {
+  let mut bytes = compile("print 1;\n").stdout;
+  bytes[SCRIPT_CODE_START + 2] = 250;
+  let output = run_bytecode("opcode", &bytes);
+  assert_eq!(output.status.code(), Some(65));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("Unknown opcode 250."), "{}", stdout);
+}
+
+#[test]
+fn rejects_functions_nested_too_deeply() {
+  // the header of a real file, then 100000 empty functions, each the only constant of the one before.
+  let depth = 100_000;
+  let mut bytes = compile("print 1;\n").stdout[..14].to_vec();
+  let empty_name_arity_and_code = [0u8; 12];
+  for _ in 0..depth {
+    bytes.extend_from_slice(&empty_name_arity_and_code);
+    bytes.extend_from_slice(&1u32.to_le_bytes()); // one constant
+    bytes.push(2); // tagged as a function
+  }
+  bytes.extend_from_slice(&empty_name_arity_and_code);
+  bytes.extend_from_slice(&0u32.to_le_bytes()); // the innermost function has no constants
+  for _ in 0..=depth {
+    bytes.extend_from_slice(&0u32.to_le_bytes()); // and none of them capture anything
+  }
+  let output = run_bytecode("nested", &bytes);
+  assert_eq!(output.status.code(), Some(65));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("Functions nested too deeply."), "{}", stdout);
+}
+
+#[test]
+fn compiles_functions_only_as_deeply_as_the_loader_accepts() {
+  let nested = |depth: usize| format!("{}{}", "fun f() { ".repeat(depth), "}".repeat(depth));
+  let bytes = compile(&nested(255)).stdout;
+  assert!(run_bytecode("deepest", &bytes).status.success());
+
+  let output = compile(&nested(256));
+  assert_eq!(output.status.code(), Some(65));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("Functions nested too deeply."), "{}", stdout);
+  assert!(stdout.contains("<stdin>:1:2555"), "{}", stdout); // the name of the 256th function
+}
+
+#[test]
+fn reports_deeply_nested_expressions_instead_of_overflowing() {
+  let depth = 100_000;
+  let expression = format!("print {}1{};\n", "(".repeat(depth), ")".repeat(depth));
+  // one block per line, so the error's snippet stays short.
+  let statement = "{\n".repeat(1_000_000);
+  let cases = [
+    (expression, "Expression nested too deeply."),
+    (statement, "Statement nested too deeply."),
+  ];
+  for (source, message) in &cases {
+    for backend in ["vm", "tree-walker"] {
+      let output = lox_with_stdin(&["--backend", backend, "run"], source);
+      assert_eq!(output.status.code(), Some(65), "{}", backend);
+      let stdout = String::from_utf8_lossy(&output.stdout);
+      assert!(stdout.contains(message), "{}", backend);
+      assert_eq!(stdout.matches("error:").count(), 1, "{}", backend);
+    }
+  }
+}
+
+#[test]
+fn points_vm_runtime_errors_at_the_source() {
+  let output = lox_with_stdin(&["run"], "var a = 1;\nprint a + nil;\n");
+  assert_eq!(output.status.code(), Some(70));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("2 | print a + nil;\n  |         ^"), "{}", stdout);
+}
+
+#[test]
+fn elides_the_middle_of_a_deep_call_trace() {
+  let output = lox_with_stdin(&["run"], "fun recurse() {\n  recurse();\n}\nrecurse();\n");
+  assert_eq!(output.status.code(), Some(70));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("Stack overflow."), "{}", stdout);
+  assert!(stdout.contains("= note: ... 994 more frames\n"), "{}", stdout);
+  assert_eq!(stdout.matches("in recurse()").count(), 5, "{}", stdout);
+  assert!(stdout.contains("[line 4] in script"), "{}", stdout);
+}
+
+#[test]
+fn reuses_the_shapes_of_collected_classes() {
+  let source = "fun make() {\n  class Box {\n    init() { this.value = 1; }\n    get() { return this.value; }\n  }\n  return Box;\n}\nvar total = 0;\nfor (var i = 0; i < 50; i = i + 1) {\n  total = total + make()().get();\n}\nprint total;\n";
+  let output = lox_with_stdin(&["--gc-stress", "--stats", "run"], source);
+  assert!(output.status.success());
+  assert_eq!(String::from_utf8_lossy(&output.stdout), "50\n");
+  // a root and a shape with `value`, for whichever `Box` is alive; 50 classes would need 100.
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(stderr.contains("shapes                  2\n"), "{}", stderr);
+}
@@ -0,0 +1,29 @@
+use std::process::Command;
+
+// runs `lox test-suite` over the `.hex` corpus in `tests/lox`, with `args` before the subcommand.
+fn assert_suite_passes(args: &[&str]) {
+  let corpus = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/lox");
+  let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+    .args(args)
+    .args(["test-suite", corpus])
+    .output()
+    .unwrap();
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(output.status.success(), "conformance failures:\n{}", stdout);
+}
+
+#[test]
+fn lox_test_suite_passes() {
+  assert_suite_passes(&[]);
+}
+
+#[test]
+fn lox_test_suite_passes_on_tree_walker() {
+  assert_suite_passes(&["--backend", "tree-walker"]);
+}
+
+// collecting before every allocation frees anything the vm forgot to root.
+#[test]
+fn lox_test_suite_passes_on_vm_under_gc_stress() {
+  assert_suite_passes(&["--gc-stress"]);
+}